@@ -117,6 +117,8 @@ case $choice in
         echo "   1. Copy a private key from wallets.txt"
         echo "   2. Edit .env and set PRIVATE_KEY_HEX=<your_private_key>"
         echo "   3. Run option 2 to start the transaction generator"
+        echo "   💡 To fund every generated wallet from your key in one go:"
+        echo "      kaspa-tx-generator --fund-wallets wallets.txt --amount 10"
         echo ""
         read -p "Press Enter to continue..."
         ;;
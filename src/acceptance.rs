@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use hdrhistogram::Histogram;
+use kaspa_consensus_core::tx::TransactionId;
+use kaspa_rpc_core::VirtualChainChangedNotification;
+use tokio::time::Instant;
+
+/// Highest submit-to-accept latency the histogram resolves, in milliseconds.
+const MAX_TRACKED_LATENCY_MS: u64 = 3_600_000;
+
+/// Matches submitted transactions against the accepted transaction ids of
+/// VirtualChainChanged notifications and records the time from submission until
+/// the virtual chain accepted them. Transactions still unaccepted after the timeout
+/// are counted as never accepted and forgotten. Everything is kept per chain depth,
+/// the number of unconfirmed ancestors a transaction had when it was submitted.
+pub struct AcceptanceTracker {
+    timeout: Duration,
+    /// Submitted and not accepted yet, with their submit time and chain depth
+    pending: Mutex<HashMap<TransactionId, (Instant, u32)>>,
+    /// Indexed by chain depth
+    by_depth: Mutex<Vec<DepthStats>>,
+    /// Set once a VirtualChainChanged subscription is up
+    active: AtomicBool,
+}
+
+struct DepthStats {
+    /// Submit-to-accept latency in milliseconds
+    latencies: Histogram<u64>,
+    accepted: u64,
+    never_accepted: u64,
+}
+
+impl DepthStats {
+    fn new() -> Self {
+        DepthStats { latencies: new_histogram(), accepted: 0, never_accepted: 0 }
+    }
+
+    fn summary(&self) -> AcceptanceSummary {
+        let at = |quantile: f64| Duration::from_millis(self.latencies.value_at_quantile(quantile));
+        AcceptanceSummary {
+            accepted: self.accepted,
+            never_accepted: self.never_accepted,
+            p50: at(0.5),
+            p90: at(0.9),
+            p99: at(0.99),
+            max: Duration::from_millis(self.latencies.max()),
+        }
+    }
+}
+
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, MAX_TRACKED_LATENCY_MS, 3).expect("valid histogram bounds")
+}
+
+/// The stats of `depth`, added on first use.
+fn at_depth(by_depth: &mut Vec<DepthStats>, depth: u32) -> &mut DepthStats {
+    let index = depth as usize;
+    while by_depth.len() <= index {
+        by_depth.push(DepthStats::new());
+    }
+    &mut by_depth[index]
+}
+
+/// Counts and latency percentiles reported by `AcceptanceTracker::summary`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcceptanceSummary {
+    pub accepted: u64,
+    pub never_accepted: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl AcceptanceTracker {
+    pub fn new(timeout: Duration) -> Self {
+        AcceptanceTracker {
+            timeout,
+            pending: Mutex::new(HashMap::new()),
+            by_depth: Mutex::new(Vec::new()),
+            active: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts the clock for `txid`, which has `chain_depth` unconfirmed ancestors.
+    /// Called before the submit, as the acceptance can be notified before the submit
+    /// call returns.
+    pub fn track(&self, txid: TransactionId, submitted_at: Instant, chain_depth: u32) {
+        self.pending.lock().unwrap().insert(txid, (submitted_at, chain_depth));
+    }
+
+    /// Stops tracking `txid`, e.g. after the node rejected it.
+    pub fn forget(&self, txid: &TransactionId) {
+        self.pending.lock().unwrap().remove(txid);
+    }
+
+    /// Records the latency of every tracked transaction the notification accepts.
+    /// Transactions that a reorg moves to another chain block are only counted once.
+    pub fn record(&self, changed: &VirtualChainChangedNotification) {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let mut by_depth = self.by_depth.lock().unwrap();
+        for txid in changed.accepted_transaction_ids.iter().flat_map(|block| &block.accepted_transaction_ids) {
+            if let Some((submitted_at, chain_depth)) = pending.remove(txid) {
+                let stats = at_depth(&mut by_depth, chain_depth);
+                stats.latencies.saturating_record((now - submitted_at).as_millis().max(1) as u64);
+                stats.accepted += 1;
+            }
+        }
+    }
+
+    /// Counts transactions pending for longer than the timeout as never accepted.
+    pub fn expire(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        let mut by_depth = self.by_depth.lock().unwrap();
+        pending.retain(|_, (submitted_at, chain_depth)| {
+            let expired = submitted_at.elapsed() > self.timeout;
+            if expired {
+                at_depth(&mut by_depth, *chain_depth).never_accepted += 1;
+            }
+            !expired
+        });
+    }
+
+    pub fn set_active(&self) {
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether acceptance notifications are flowing; without them every transaction
+    /// would end up counted as never accepted.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn accepted(&self) -> u64 {
+        self.by_depth.lock().unwrap().iter().map(|stats| stats.accepted).sum()
+    }
+
+    /// Whether `txid` was submitted by us and is not accepted yet.
+    pub fn is_pending(&self, txid: &TransactionId) -> bool {
+        self.pending.lock().unwrap().contains_key(txid)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Waits up to the timeout for the pending transactions to be accepted, then
+    /// counts the rest as never accepted.
+    pub async fn settle(&self) {
+        let started = Instant::now();
+        while self.is_active() && self.pending() > 0 && started.elapsed() < self.timeout {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let mut by_depth = self.by_depth.lock().unwrap();
+        for (_, chain_depth) in pending.drain().map(|(_, tracked)| tracked) {
+            at_depth(&mut by_depth, chain_depth).never_accepted += 1;
+        }
+    }
+
+    /// Counts and latencies over all chain depths.
+    pub fn summary(&self) -> AcceptanceSummary {
+        let mut total = DepthStats::new();
+        for stats in self.by_depth.lock().unwrap().iter() {
+            total.latencies.add(&stats.latencies).expect("histograms with equal bounds");
+            total.accepted += stats.accepted;
+            total.never_accepted += stats.never_accepted;
+        }
+        total.summary()
+    }
+
+    /// Counts and latencies per chain depth, indexed by depth.
+    pub fn summary_by_depth(&self) -> Vec<AcceptanceSummary> {
+        self.by_depth.lock().unwrap().iter().map(DepthStats::summary).collect()
+    }
+}
+
+impl AcceptanceSummary {
+    /// One line for the end-of-run summary, e.g. `p50 1.2s, p90 2.0s, p99 3.1s, max 4.5s`.
+    pub fn describe_latency(&self) -> String {
+        format!(
+            "p50 {:.1}s, p90 {:.1}s, p99 {:.1}s, max {:.1}s",
+            self.p50.as_secs_f64(),
+            self.p90.as_secs_f64(),
+            self.p99.as_secs_f64(),
+            self.max.as_secs_f64()
+        )
+    }
+}
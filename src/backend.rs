@@ -0,0 +1,166 @@
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use kaspa_grpc_client::GrpcClient;
+use kaspa_notify::{listener::ListenerId, scope::Scope};
+use kaspa_rpc_core::{
+    api::rpc::RpcApi,
+    notify::connection::{ChannelConnection, ChannelType},
+    Notification, RpcError, RpcResult,
+};
+use kaspa_wrpc_client::{
+    client::{ConnectOptions, ConnectStrategy},
+    KaspaRpcClient, WrpcEncoding,
+};
+
+const WRPC_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ----------------------- encoding -----------------------
+/// Message encoding for `ws://` and `wss://` endpoints (`WRPC_ENCODING`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Borsh,
+    Json,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "borsh" => Ok(Encoding::Borsh),
+            "json" => Ok(Encoding::Json),
+            _ => Err(format!("Unknown wRPC encoding: {}. Supported: borsh, json", s)),
+        }
+    }
+}
+
+// ----------------------- backends -----------------------
+/// A connection to a node over one of the supported transports. Requests go
+/// through `api()`; connection handling and notifications are transport specific.
+#[async_trait]
+pub trait RpcBackend: Send + Sync {
+    /// Transport name for logs, e.g. "gRPC" or "wRPC/Borsh".
+    fn transport(&self) -> &'static str;
+
+    fn api(&self) -> &dyn RpcApi;
+
+    fn is_connected(&self) -> bool;
+
+    async fn disconnect(&self) -> RpcResult<()>;
+
+    /// Subscribes to `scope`. The returned channel is shared by every subscription
+    /// on this backend, so consumers should match on the notification variant.
+    async fn subscribe(&self, scope: Scope) -> RpcResult<Receiver<Notification>>;
+}
+
+pub struct GrpcBackend {
+    client: GrpcClient,
+}
+
+impl GrpcBackend {
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let client = GrpcClient::connect(url.to_string()).await?;
+        client.start(None).await;
+        Ok(GrpcBackend { client })
+    }
+}
+
+#[async_trait]
+impl RpcBackend for GrpcBackend {
+    fn transport(&self) -> &'static str {
+        "gRPC"
+    }
+
+    fn api(&self) -> &dyn RpcApi {
+        &self.client
+    }
+
+    fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    async fn disconnect(&self) -> RpcResult<()> {
+        self.client.disconnect().await.map_err(|e| RpcError::General(e.to_string()))
+    }
+
+    async fn subscribe(&self, scope: Scope) -> RpcResult<Receiver<Notification>> {
+        // The client runs in direct notification mode: a single listener, one channel
+        self.client.start_notify(ListenerId::default(), scope).await?;
+        Ok(self.client.notification_channel_receiver())
+    }
+}
+
+pub struct WrpcBackend {
+    client: KaspaRpcClient,
+    encoding: Encoding,
+    listener: OnceLock<ListenerId>,
+    notifications: (Sender<Notification>, Receiver<Notification>),
+}
+
+impl WrpcBackend {
+    pub async fn connect(url: &str, encoding: Encoding) -> Result<Self, Box<dyn Error>> {
+        let wrpc_encoding = match encoding {
+            Encoding::Borsh => WrpcEncoding::Borsh,
+            Encoding::Json => WrpcEncoding::SerdeJson,
+        };
+        let client = KaspaRpcClient::new(wrpc_encoding, Some(url), None, None, None)?;
+        let options = ConnectOptions {
+            block_async_connect: true,
+            strategy: ConnectStrategy::Fallback,
+            connect_timeout: Some(WRPC_CONNECT_TIMEOUT),
+            ..Default::default()
+        };
+        client.connect(Some(options)).await?;
+        client.start().await?;
+        Ok(WrpcBackend { client, encoding, listener: OnceLock::new(), notifications: async_channel::unbounded() })
+    }
+}
+
+#[async_trait]
+impl RpcBackend for WrpcBackend {
+    fn transport(&self) -> &'static str {
+        match self.encoding {
+            Encoding::Borsh => "wRPC/Borsh",
+            Encoding::Json => "wRPC/JSON",
+        }
+    }
+
+    fn api(&self) -> &dyn RpcApi {
+        &self.client
+    }
+
+    fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    async fn disconnect(&self) -> RpcResult<()> {
+        self.client.disconnect().await.map_err(|e| RpcError::General(e.to_string()))
+    }
+
+    async fn subscribe(&self, scope: Scope) -> RpcResult<Receiver<Notification>> {
+        let listener = *self.listener.get_or_init(|| {
+            let connection = ChannelConnection::new("kaspa-tx-generator", self.notifications.0.clone(), ChannelType::Persistent);
+            self.client.register_new_listener(connection)
+        });
+        self.client.start_notify(listener, scope).await?;
+        Ok(self.notifications.1.clone())
+    }
+}
+
+/// Connects to `url`, picking the transport from its scheme: `grpc://` for gRPC,
+/// `ws://` or `wss://` for wRPC with the given encoding.
+pub async fn connect(url: &str, encoding: Encoding) -> Result<Arc<dyn RpcBackend>, Box<dyn Error>> {
+    let backend: Arc<dyn RpcBackend> = match url.split_once("://").map(|(scheme, _)| scheme) {
+        Some("grpc") => Arc::new(GrpcBackend::connect(url).await?),
+        Some("ws") | Some("wss") => Arc::new(WrpcBackend::connect(url, encoding).await?),
+        _ => return Err(format!("Unsupported RPC URL {}: expected grpc://, ws:// or wss://", url).into()),
+    };
+    Ok(backend)
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashMap, error::Error};
+
+use kaspa_addresses::Address;
+use kaspa_txscript::pay_to_address_script;
+use serde::Serialize;
+
+use crate::{
+    cli,
+    config::Config,
+    keys, preflight, rpc,
+    tx::format_kas,
+    utxo::{self, Maturity, Utxo},
+    wallets,
+};
+
+/// What the node holds for one address. Immature outputs are non-coinbase outputs
+/// that still wait for `NON_COINBASE_CONFIRMATIONS` before they can be spent;
+/// pending outputs pay the address from mempool transactions and are not part of
+/// the balance yet.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Balance {
+    pub balance_sompi: u64,
+    pub utxos: usize,
+    pub spendable_sompi: u64,
+    pub mature_coinbase_sompi: u64,
+    pub immature_coinbase_sompi: u64,
+    pub immature_outputs: usize,
+    pub immature_sompi: u64,
+    pub pending_outputs: usize,
+    pub pending_sompi: u64,
+}
+
+impl Balance {
+    fn add_utxo(&mut self, utxo: &Utxo, virtual_daa_score: u64, maturity: Maturity) {
+        let entry = &utxo.1;
+        let spendable = utxo::is_utxo_spendable(entry, virtual_daa_score, maturity);
+        self.balance_sompi += entry.amount;
+        self.utxos += 1;
+        if spendable {
+            self.spendable_sompi += entry.amount;
+        }
+        if entry.is_coinbase {
+            if spendable {
+                self.mature_coinbase_sompi += entry.amount;
+            } else {
+                self.immature_coinbase_sompi += entry.amount;
+            }
+        } else if !spendable {
+            self.immature_outputs += 1;
+            self.immature_sompi += entry.amount;
+        }
+    }
+
+    fn add_pending(&mut self, amount: u64) {
+        self.pending_outputs += 1;
+        self.pending_sompi += amount;
+    }
+
+    fn add(&mut self, other: &Balance) {
+        self.balance_sompi += other.balance_sompi;
+        self.utxos += other.utxos;
+        self.spendable_sompi += other.spendable_sompi;
+        self.mature_coinbase_sompi += other.mature_coinbase_sompi;
+        self.immature_coinbase_sompi += other.immature_coinbase_sompi;
+        self.immature_outputs += other.immature_outputs;
+        self.immature_sompi += other.immature_sompi;
+        self.pending_outputs += other.pending_outputs;
+        self.pending_sompi += other.pending_sompi;
+    }
+}
+
+#[derive(Serialize)]
+struct AddressBalance {
+    address: String,
+    #[serde(flatten)]
+    balance: Balance,
+}
+
+#[derive(Serialize)]
+struct Report {
+    network: String,
+    virtual_daa_score: u64,
+    addresses: Vec<AddressBalance>,
+    total: Balance,
+}
+
+/// Reads `target` as an address, or else as a `--gen-wallets` file whose addresses
+/// are derived from its keys.
+fn addresses(config: &Config, target: &str) -> Result<Vec<Address>, Box<dyn Error>> {
+    if Address::try_from(target).is_ok() {
+        return Ok(vec![keys::parse_address(target, config.network)?]);
+    }
+    let keypairs = wallets::load_keys(target)
+        .map_err(|e| format!("{} is neither an address nor a readable wallet file: {}", target, e))?;
+    Ok(keypairs.iter().map(|keypair| keys::address_for(keypair, config.network)).collect())
+}
+
+/// `--balances <wallet-file|address> [--json]`: prints what the node holds for each
+/// address, plus totals. Read-only, so no key or mainnet confirmation is needed.
+pub async fn run(config: &Config, target: &str, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let addresses = addresses(config, target)?;
+    if addresses.is_empty() {
+        return Err(format!("No wallets in {}", target).into());
+    }
+
+    let client = rpc::connect(&config.rpc_url, config.wrpc_encoding).await?;
+    preflight::check(&*client, config.network).await?;
+    let entries = client.api().get_utxos_by_addresses(addresses.clone()).await?;
+    let mempool = client.api().get_mempool_entries_by_addresses(addresses.clone(), false, false).await?;
+    let virtual_daa_score = rpc::virtual_daa_score(&*client).await?;
+
+    let mut by_script = HashMap::new();
+    for entry in entries {
+        let utxo: Utxo = (entry.outpoint.into(), entry.utxo_entry.into());
+        by_script.entry(utxo.1.script_public_key.clone()).or_insert_with(Vec::new).push(utxo);
+    }
+    // Outputs of mempool transactions paying each address
+    let mut pending_by_script = HashMap::new();
+    for entry in mempool {
+        let script_public_key = pay_to_address_script(&entry.address);
+        let amounts: Vec<u64> = entry
+            .receiving
+            .iter()
+            .flat_map(|mempool_entry| &mempool_entry.transaction.outputs)
+            .filter(|output| output.script_public_key == script_public_key)
+            .map(|output| output.value)
+            .collect();
+        pending_by_script.insert(script_public_key, amounts);
+    }
+    let mut total = Balance::default();
+    let mut rows = Vec::with_capacity(addresses.len());
+    for address in &addresses {
+        let script_public_key = pay_to_address_script(address);
+        let mut balance = Balance::default();
+        for utxo in by_script.get(&script_public_key).into_iter().flatten() {
+            balance.add_utxo(utxo, virtual_daa_score, config.maturity());
+        }
+        for &amount in pending_by_script.get(&script_public_key).into_iter().flatten() {
+            balance.add_pending(amount);
+        }
+        total.add(&balance);
+        rows.push(AddressBalance { address: String::from(address), balance });
+    }
+
+    if cli::has_flag(args, "--json") {
+        let report = Report { network: config.network.to_string(), virtual_daa_score, addresses: rows, total };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "💰 Balances of {} addresses on {} (virtual DAA score {})",
+        addresses.len(),
+        config.network,
+        virtual_daa_score
+    );
+    for (index, row) in rows.iter().enumerate() {
+        println!("   {:>4} {}: {}", index + 1, row.address, describe(&row.balance));
+    }
+    println!();
+    println!("   Total:     {}", describe(&total));
+    println!("   Spendable: {}", format_kas(total.spendable_sompi));
+    Ok(())
+}
+
+fn describe(balance: &Balance) -> String {
+    let mut line = format!("{} in {} UTXOs", format_kas(balance.balance_sompi), balance.utxos);
+    if balance.mature_coinbase_sompi + balance.immature_coinbase_sompi > 0 {
+        line += &format!(
+            ", coinbase {} mature / {} immature",
+            format_kas(balance.mature_coinbase_sompi),
+            format_kas(balance.immature_coinbase_sompi)
+        );
+    }
+    if balance.immature_outputs > 0 {
+        line += &format!(", {} immature in {} outputs", format_kas(balance.immature_sompi), balance.immature_outputs);
+    }
+    if balance.pending_outputs > 0 {
+        line +=
+            &format!(", {} pending in {} mempool outputs", format_kas(balance.pending_sompi), balance.pending_outputs);
+    }
+    line
+}
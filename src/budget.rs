@@ -0,0 +1,109 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::tx::format_kas;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a fee was not granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The fee would exceed `MAX_TOTAL_FEES_SOMPI`; the run should stop.
+    Exhausted,
+    /// The fee would exceed `MAX_FEES_PER_MINUTE_SOMPI`; retry later.
+    PerMinute,
+}
+
+/// A fee granted by `FeeBudget::try_spend`, handed back to `FeeBudget::refund` if
+/// the transaction it paid for is not accepted.
+#[derive(Debug)]
+pub struct Grant {
+    id: u64,
+    fee: u64,
+}
+
+struct BudgetState {
+    spent: u64,
+    /// Fees granted within the last minute, by grant id
+    window: VecDeque<(Instant, u64, u64)>,
+    next_id: u64,
+}
+
+/// Caps the fees a run may spend, in total and per rolling minute. Fees are granted
+/// before a transaction is submitted and refunded if it is rejected. A limit of 0
+/// means unlimited.
+pub struct FeeBudget {
+    total_limit: u64,
+    per_minute_limit: u64,
+    state: Mutex<BudgetState>,
+}
+
+impl FeeBudget {
+    pub fn new(total_limit: u64, per_minute_limit: u64) -> Self {
+        FeeBudget {
+            total_limit,
+            per_minute_limit,
+            state: Mutex::new(BudgetState { spent: 0, window: VecDeque::new(), next_id: 0 }),
+        }
+    }
+
+    pub fn try_spend(&self, fee: u64) -> Result<Grant, Refusal> {
+        let mut state = self.state.lock().unwrap();
+        if self.total_limit > 0 && state.spent + fee > self.total_limit {
+            return Err(Refusal::Exhausted);
+        }
+        let now = Instant::now();
+        while state.window.front().is_some_and(|(granted, _, _)| now.duration_since(*granted) >= WINDOW) {
+            state.window.pop_front();
+        }
+        if self.per_minute_limit > 0 {
+            let last_minute: u64 = state.window.iter().map(|(_, _, fee)| fee).sum();
+            if last_minute + fee > self.per_minute_limit {
+                return Err(Refusal::PerMinute);
+            }
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.spent += fee;
+        state.window.push_back((now, id, fee));
+        Ok(Grant { id, fee })
+    }
+
+    /// Like `try_spend`, but waits out the per-minute ceiling instead of refusing.
+    pub async fn spend(&self, fee: u64) -> Result<Grant, Refusal> {
+        loop {
+            match self.try_spend(fee) {
+                Err(Refusal::PerMinute) => tokio::time::sleep(Duration::from_secs(1)).await,
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns a fee granted for a transaction that was not accepted.
+    pub fn refund(&self, grant: Grant) {
+        let mut state = self.state.lock().unwrap();
+        state.spent = state.spent.saturating_sub(grant.fee);
+        if let Some(index) = state.window.iter().position(|(_, id, _)| *id == grant.id) {
+            state.window.remove(index);
+        }
+    }
+
+    pub fn spent(&self) -> u64 {
+        self.state.lock().unwrap().spent
+    }
+
+    /// Fees spent against the limits, for reports.
+    pub fn describe(&self) -> String {
+        let mut description = format_kas(self.spent());
+        if self.total_limit > 0 {
+            description += &format!(" of {} budget", format_kas(self.total_limit));
+        }
+        if self.per_minute_limit > 0 {
+            description += &format!(", at most {} per minute", format_kas(self.per_minute_limit));
+        }
+        description
+    }
+}
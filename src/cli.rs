@@ -0,0 +1,42 @@
+/// Returns true if `flag` was passed on the command line.
+pub fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+/// Returns the value of `name`, accepting both `--name value` and `--name=value`.
+pub fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    for (i, arg) in args.iter().enumerate() {
+        if arg == name {
+            return args.get(i + 1).cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// Parses the value of `name`, returning an error if it is present but invalid.
+pub fn parse_arg<T>(args: &[String], name: &str) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match arg_value(args, name) {
+        Some(value) => value.parse::<T>().map(Some).map_err(|e| format!("Invalid value for {}: {} ({})", name, value, e)),
+        None => Ok(None),
+    }
+}
+
+/// Rejects `--` flags not in `known`, so that a mistyped command is reported
+/// instead of falling through to the default spam run.
+pub fn check_flags(args: &[String], known: &[&str]) -> Result<(), String> {
+    for arg in args.iter().filter(|arg| arg.starts_with("--")) {
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        if !known.contains(&name) {
+            return Err(format!("Unknown option {}; run with --help for the list of options", name));
+        }
+    }
+    Ok(())
+}
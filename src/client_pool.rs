@@ -0,0 +1,206 @@
+use std::{
+    error::Error,
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock, Weak,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::backend::{self, Encoding, RpcBackend};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Start of the error `ClientPool::call` returns once it runs out of attempts.
+const UNREACHABLE: &str = "No connection to";
+
+// ----------------------- selection -----------------------
+/// How the pool picks a connection for each request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    RoundRobin,
+    LeastInflight,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "round-robin" | "rr" => Ok(Selection::RoundRobin),
+            "least-inflight" | "least" => Ok(Selection::LeastInflight),
+            _ => Err(format!("Unknown client selection: {}. Supported: round-robin, least-inflight", s)),
+        }
+    }
+}
+
+// ----------------------- pool -----------------------
+struct Slot {
+    /// `None` while the connection is down and being re-established
+    client: RwLock<Option<Arc<dyn RpcBackend>>>,
+    inflight: AtomicUsize,
+    reconnecting: AtomicBool,
+}
+
+/// A fixed set of connections to the same node. Dropped connections are
+/// reconnected in the background with exponential backoff, and requests that
+/// failed because of a dropped connection are retried on another one.
+pub struct ClientPool {
+    url: String,
+    encoding: Encoding,
+    selection: Selection,
+    slots: Vec<Slot>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    /// Opens `size` connections to `url`. Fails only if none of them can be opened;
+    /// the others keep retrying in the background.
+    pub async fn connect(
+        url: &str,
+        encoding: Encoding,
+        size: usize,
+        selection: Selection,
+    ) -> Result<Arc<Self>, Box<dyn Error>> {
+        let mut slots = Vec::with_capacity(size.max(1));
+        let mut last_error = None;
+        for _ in 0..size.max(1) {
+            let client = match backend::connect(url, encoding).await {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    last_error = Some(e.to_string());
+                    None
+                }
+            };
+            slots.push(Slot { client: RwLock::new(client), inflight: AtomicUsize::new(0), reconnecting: AtomicBool::new(false) });
+        }
+        let pool = Arc::new(ClientPool { url: url.to_string(), encoding, selection, slots, next: AtomicUsize::new(0) });
+        let Some((_, client)) = pool.pick() else {
+            return Err(format!("Failed to connect to {}: {}", url, last_error.unwrap_or_default()).into());
+        };
+        info!("Connected {}/{} connections to {} over {}", pool.connected(), pool.slots.len(), url, client.transport());
+        for index in 0..pool.slots.len() {
+            if pool.slots[index].client.read().unwrap().is_none() {
+                pool.reconnect(index);
+            }
+        }
+        tokio::spawn(health_check(Arc::downgrade(&pool)));
+        Ok(pool)
+    }
+
+    /// Number of connections currently up.
+    pub fn connected(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.client.read().unwrap().is_some()).count()
+    }
+
+    fn pick(&self) -> Option<(usize, Arc<dyn RpcBackend>)> {
+        let len = self.slots.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut candidates = (0..len).map(|offset| (start + offset) % len).filter_map(|index| {
+            let client = self.slots[index].client.read().unwrap().clone()?;
+            Some((index, client))
+        });
+        match self.selection {
+            Selection::RoundRobin => candidates.next(),
+            Selection::LeastInflight => {
+                candidates.min_by_key(|(index, _)| self.slots[*index].inflight.load(Ordering::Relaxed))
+            }
+        }
+    }
+
+    /// Runs `op` on a pooled connection. If it fails and the connection turns out
+    /// to be down, the connection is reconnected in the background and `op` is
+    /// requeued on another one. Errors from a healthy connection (e.g. a rejected
+    /// transaction) are returned as is. Submits are requeued too: the node answers a
+    /// transaction it already holds with "already in the mempool", which callers
+    /// count as submitted.
+    pub async fn call<T, F, Fut>(self: &Arc<Self>, op: F) -> Result<T, Box<dyn Error>>
+    where
+        F: Fn(Arc<dyn RpcBackend>) -> Fut,
+        Fut: Future<Output = Result<T, Box<dyn Error>>>,
+    {
+        let attempts = self.slots.len() * 2;
+        for _ in 0..attempts {
+            let Some((index, client)) = self.pick() else {
+                tokio::time::sleep(INITIAL_BACKOFF).await;
+                continue;
+            };
+            let slot = &self.slots[index];
+            slot.inflight.fetch_add(1, Ordering::Relaxed);
+            let result = op(client.clone()).await;
+            slot.inflight.fetch_sub(1, Ordering::Relaxed);
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if client.is_connected() => return Err(e),
+                Err(e) => {
+                    warn!("Connection {} to {} dropped ({}), requeueing request", index, self.url, e);
+                    self.reconnect(index);
+                }
+            }
+        }
+        Err(format!("{} {} available after {} attempts", UNREACHABLE, self.url, attempts).into())
+    }
+
+    /// Takes connection `index` out of rotation and re-establishes it in the
+    /// background, unless that is already under way.
+    fn reconnect(self: &Arc<Self>, index: usize) {
+        let slot = &self.slots[index];
+        if slot.reconnecting.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let stale = slot.client.write().unwrap().take();
+        let pool = self.clone();
+        tokio::spawn(async move {
+            if let Some(stale) = stale {
+                let _ = stale.disconnect().await;
+            }
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                tokio::time::sleep(backoff).await;
+                match backend::connect(&pool.url, pool.encoding).await {
+                    Ok(client) => {
+                        *pool.slots[index].client.write().unwrap() = Some(client);
+                        pool.slots[index].reconnecting.store(false, Ordering::SeqCst);
+                        info!("Reconnected connection {} to {}", index, pool.url);
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("Reconnecting connection {} to {} failed: {} (retry in {:?})", index, pool.url, e, backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Whether `error` is `ClientPool::call` running out of connections. A request may
+/// have reached the node before its connection dropped, so its outcome is unknown.
+pub fn is_unreachable(error: &str) -> bool {
+    error.starts_with(UNREACHABLE)
+}
+
+/// Pings every live connection periodically and reconnects the ones that fail.
+/// Stops once the pool is dropped.
+async fn health_check(pool: Weak<ClientPool>) {
+    loop {
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+        for index in 0..pool.slots.len() {
+            let client = pool.slots[index].client.read().unwrap().clone();
+            if let Some(client) = client {
+                if let Err(e) = client.api().ping().await {
+                    warn!("Health check of connection {} to {} failed: {}", index, pool.url, e);
+                    pool.reconnect(index);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{env, error::Error, str::FromStr};
+
+use kaspa_addresses::Prefix;
+use kaspa_consensus_core::network::{NetworkId, NetworkType};
+
+use crate::{
+    backend::Encoding,
+    cli,
+    client_pool::Selection,
+    distribution::Distribution,
+    fees::FeeTier,
+    profile::{Curve, Profile},
+    shape::ShapeMix,
+    utxo::Maturity,
+};
+
+/// TPS ceiling when `UNLEASHED=false`.
+const SAFE_TPS_CAP: f64 = 100.0;
+
+// ----------------------- network -----------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet10,
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" | "main" => Ok(Network::Mainnet),
+            "testnet10" | "testnet-10" | "tn10" => Ok(Network::Testnet10),
+            _ => Err(format!("Unknown network: {}. Supported networks: mainnet, testnet10 (tn10)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet10 => write!(f, "testnet-10"),
+        }
+    }
+}
+
+impl Network {
+    pub fn default_grpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "grpc://n-mainnet.kaspa.ws:16110",
+            Network::Testnet10 => "grpc://n-testnet-10.kaspa.ws:16210",
+        }
+    }
+
+    pub fn address_prefix(&self) -> Prefix {
+        match self {
+            Network::Mainnet => Prefix::Mainnet,
+            Network::Testnet10 => Prefix::Testnet,
+        }
+    }
+
+    pub fn network_id(&self) -> NetworkId {
+        match self {
+            Network::Mainnet => NetworkId::new(NetworkType::Mainnet),
+            Network::Testnet10 => NetworkId::with_suffix(NetworkType::Testnet, 10),
+        }
+    }
+}
+
+// ----------------------- configuration -----------------------
+/// Run configuration, read from the environment (see `env.example`).
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub private_key_hex: Option<String>,
+    pub network: Network,
+    pub rpc_url: String,
+    pub wrpc_encoding: Encoding,
+    pub target_utxo_count: usize,
+    pub amount_per_utxo: u64,
+    pub outputs_per_transaction: usize,
+    pub spam_duration_seconds: u64,
+    pub target_tps: u64,
+    /// Target TPS over time; overrides `TARGET_TPS` when set
+    pub tps_profile: Option<Curve>,
+    pub unleashed: bool,
+    pub millis_per_tick: u64,
+    pub base_fee_rate: u64,
+    pub fee_tier: FeeTier,
+    pub fee_refresh_secs: u64,
+    /// Per-transaction fee rate in sompi per gram; overrides `FEE_TIER` when set
+    pub fee_rate_distribution: Option<Distribution>,
+    /// Per-transaction payload size in bytes
+    pub payload_size_distribution: Option<Distribution>,
+    /// Weighted mix of spam transaction shapes
+    pub tx_shapes: ShapeMix,
+    /// CSV file receiving one row per spam transaction
+    pub tx_log_file: Option<String>,
+    /// `--gen-wallets` file whose wallets send the spam instead of the master key
+    pub wallets_file: Option<String>,
+    pub client_pool_size: usize,
+    pub client_selection: Selection,
+    pub utxo_refresh_secs: u64,
+    /// Reconciliation poll interval while UtxosChanged notifications keep the pools current
+    pub utxo_reconcile_secs: u64,
+    pub min_change_sompi: u64,
+    pub max_pending_age_secs: u64,
+    pub coinbase_maturity: u64,
+    /// Confirmations (in DAA score) a non-coinbase output needs before we spend it
+    pub non_coinbase_confirmations: u64,
+    /// Spendable UTXOs per sender below which large outputs are split during the run (0 = off)
+    pub replenish_low_water: usize,
+    pub expected_confirmation_secs: u64,
+    /// Transactions not accepted by the virtual chain within this long count as never accepted
+    pub acceptance_timeout_secs: u64,
+    /// Most unconfirmed ancestors a spam transaction may have (0 = spend confirmed outputs only)
+    pub chain_depth: u32,
+    /// Total fee budget for the run in sompi (0 = unlimited)
+    pub max_total_fees_sompi: u64,
+    /// Fee ceiling per rolling minute in sompi (0 = unlimited)
+    pub max_fees_per_minute_sompi: u64,
+    /// `--i-understand-mainnet` was passed
+    pub mainnet_confirmed: bool,
+}
+
+impl Config {
+    /// Reads the configuration from environment variables. `--net` on the command
+    /// line takes precedence over `KASPA_NETWORK`.
+    pub fn from_env(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let network = match cli::arg_value(args, "--net") {
+            Some(net) => net.parse::<Network>()?,
+            None => env_or("KASPA_NETWORK", Network::Testnet10)?,
+        };
+        let rpc_url = env::var("KASPA_RPC_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .or_else(|| env::var("KASPA_GRPC_URL").ok().filter(|url| !url.is_empty()))
+            .unwrap_or_else(|| network.default_grpc_url().to_string());
+        let private_key_hex = env::var("PRIVATE_KEY_HEX")
+            .ok()
+            .filter(|key| !key.is_empty() && key != "ENTER_YOUR_PRIVATE_KEY_HERE");
+
+        Ok(Config {
+            private_key_hex,
+            network,
+            rpc_url,
+            wrpc_encoding: env_or("WRPC_ENCODING", Encoding::Borsh)?,
+            target_utxo_count: env_or("TARGET_UTXO_COUNT", 100)?,
+            amount_per_utxo: env_or("AMOUNT_PER_UTXO", 150_000_000)?,
+            outputs_per_transaction: env_or("OUTPUTS_PER_TRANSACTION", 10)?,
+            spam_duration_seconds: env_or("SPAM_DURATION_SECONDS", 86_400)?,
+            target_tps: env_or("TARGET_TPS", 50)?,
+            tps_profile: env_opt("TPS_PROFILE")?,
+            unleashed: env_or("UNLEASHED", true)?,
+            millis_per_tick: env_or("MILLIS_PER_TICK", 10)?,
+            base_fee_rate: env_or("BASE_FEE_RATE", 1)?,
+            fee_tier: env_or("FEE_TIER", FeeTier::Normal)?,
+            fee_refresh_secs: env_or("FEE_REFRESH_SECS", 10)?,
+            fee_rate_distribution: env_opt("FEE_RATE_DIST")?,
+            payload_size_distribution: env_opt("PAYLOAD_SIZE_DIST")?,
+            tx_shapes: env_or("TX_SHAPES", ShapeMix::default())?,
+            tx_log_file: env::var("TX_LOG_FILE").ok().filter(|path| !path.trim().is_empty()),
+            wallets_file: env::var("WALLETS_FILE").ok().filter(|path| !path.trim().is_empty()),
+            client_pool_size: env_or("CLIENT_POOL_SIZE", 8)?,
+            client_selection: env_or("CLIENT_SELECTION", Selection::RoundRobin)?,
+            utxo_refresh_secs: env_or("UTXO_REFRESH_SECS", 1)?,
+            utxo_reconcile_secs: env_or("UTXO_RECONCILE_SECS", 30)?,
+            min_change_sompi: env_or("MIN_CHANGE_SOMPI", 1_000_000)?,
+            max_pending_age_secs: env_or("MAX_PENDING_AGE_SECS", 3600)?,
+            coinbase_maturity: env_or("COINBASE_MATURITY", 1000)?,
+            non_coinbase_confirmations: env_or("NON_COINBASE_CONFIRMATIONS", 10)?,
+            replenish_low_water: env_or("REPLENISH_LOW_WATER", 0)?,
+            expected_confirmation_secs: env_or("EXPECTED_CONFIRMATION_SECS", 5)?,
+            acceptance_timeout_secs: env_or("ACCEPTANCE_TIMEOUT_SECS", 60)?,
+            chain_depth: env_or("CHAIN_DEPTH", 0)?,
+            max_total_fees_sompi: env_or("MAX_TOTAL_FEES_SOMPI", 0)?,
+            max_fees_per_minute_sompi: env_or("MAX_FEES_PER_MINUTE_SOMPI", 0)?,
+            mainnet_confirmed: cli::has_flag(args, "--i-understand-mainnet"),
+        })
+    }
+
+    /// `TPS_PROFILE`, or a constant `TARGET_TPS`, with the safety cap applied when
+    /// not unleashed.
+    pub fn traffic_profile(&self) -> Profile {
+        let curve = self.tps_profile.clone().unwrap_or(Curve::Constant(self.target_tps as f64));
+        Profile::new(curve, self.tps_cap())
+    }
+
+    /// Confirmations our outputs need before they are spent.
+    pub fn maturity(&self) -> Maturity {
+        Maturity { coinbase: self.coinbase_maturity, non_coinbase: self.non_coinbase_confirmations }
+    }
+
+    /// The safety cap on TPS, unless unleashed.
+    pub fn tps_cap(&self) -> Option<f64> {
+        (!self.unleashed).then_some(SAFE_TPS_CAP)
+    }
+
+    /// Modes that submit transactions call this first: mainnet spends real KAS, so
+    /// it needs `--i-understand-mainnet`.
+    pub fn require_mainnet_confirmation(&self) -> Result<(), Box<dyn Error>> {
+        if self.network == Network::Mainnet && !self.mainnet_confirmed {
+            return Err("Refusing to submit on mainnet, which spends real KAS. \
+                Pass --i-understand-mainnet to confirm, or select a testnet with --net tn10."
+                .into());
+        }
+        Ok(())
+    }
+
+    pub fn private_key_hex(&self) -> Result<&str, Box<dyn Error>> {
+        self.private_key_hex
+            .as_deref()
+            .ok_or_else(|| "PRIVATE_KEY_HEX is not set. Copy env.example to .env and set your private key.".into())
+    }
+}
+
+fn env_opt<T>(key: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(key) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<T>()
+            .map(Some)
+            .map_err(|e| format!("Invalid value for {}: {} ({})", key, value, e).into()),
+        _ => Ok(None),
+    }
+}
+
+fn env_or<T>(key: &str, default: T) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(key) {
+        Ok(value) if !value.trim().is_empty() => {
+            value.trim().parse::<T>().map_err(|e| format!("Invalid value for {}: {} ({})", key, value, e).into())
+        }
+        _ => Ok(default),
+    }
+}
@@ -0,0 +1,338 @@
+use std::{error::Error, time::Duration};
+
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{TransactionOutpoint, TransactionOutput, UtxoEntry};
+use kaspa_txscript::pay_to_address_script;
+use log::{info, warn};
+use secp256k1::Keypair;
+
+use crate::{
+    backend::RpcBackend,
+    budget::FeeBudget,
+    fees::FeeEstimator,
+    cli,
+    config::Config,
+    keys, preflight, rpc,
+    tx::{self, format_kas},
+    utxo::{Utxo, UtxoPool},
+    wallets,
+};
+
+/// How long to wait for a consolidation round to confirm before giving up.
+const ROUND_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A group of inputs merged into a single output.
+struct Batch {
+    utxos: Vec<Utxo>,
+    fee: u64,
+    output_amount: u64,
+}
+
+#[derive(Default)]
+struct Summary {
+    transactions: usize,
+    inputs: usize,
+    amount: u64,
+    fees: u64,
+    budget_exhausted: bool,
+}
+
+/// Where and how `--consolidate` and `--sweep-to` move our UTXOs.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Destination of a consolidation; our own address if `None`
+    pub to: Option<String>,
+    /// Destination of a sweep; takes precedence over `to`
+    pub sweep_to: Option<String>,
+    pub max_inputs: Option<usize>,
+}
+
+impl Options {
+    pub fn from_args(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        Ok(Options {
+            to: cli::arg_value(args, "--to"),
+            sweep_to: cli::arg_value(args, "--sweep-to"),
+            max_inputs: cli::parse_arg(args, "--max-inputs")?,
+        })
+    }
+}
+
+/// `--consolidate [--to ADDRESS]` merges our UTXOs into as few outputs as possible,
+/// running further rounds until a single UTXO remains when paying to ourselves.
+/// `--sweep-to ADDRESS` sends every spendable UTXO to `ADDRESS` in one round.
+pub async fn run(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let budget = FeeBudget::new(config.max_total_fees_sompi, config.max_fees_per_minute_sompi);
+    consolidate(config, &Options::from_args(args)?, &budget).await
+}
+
+/// Runs a consolidation or sweep, charging its fees to `budget`.
+pub async fn consolidate(config: &Config, options: &Options, budget: &FeeBudget) -> Result<(), Box<dyn Error>> {
+    config.require_mainnet_confirmation()?;
+    let keypair = keys::keypair_from_hex(config.private_key_hex()?)?;
+    let own_address = keys::address_for(&keypair, config.network);
+    let max_inputs =
+        options.max_inputs.unwrap_or(tx::MAX_INPUTS_PER_TRANSACTION).clamp(1, tx::MAX_INPUTS_PER_TRANSACTION);
+
+    let (destination, sweep) = match &options.sweep_to {
+        Some(address) => (keys::parse_address(address, config.network)?, true),
+        None => match &options.to {
+            Some(address) => (keys::parse_address(address, config.network)?, false),
+            None => (own_address.clone(), false),
+        },
+    };
+    let to_self = destination == own_address;
+
+    if sweep {
+        println!("🧹 Sweeping {} to {}", String::from(&own_address), String::from(&destination));
+    } else {
+        println!("🧩 Consolidating {} into {}", String::from(&own_address), String::from(&destination));
+    }
+
+    let client = rpc::connect(&config.rpc_url, config.wrpc_encoding).await?;
+    preflight::check(&*client, config.network).await?;
+    let pool = UtxoPool::new(config.maturity(), Duration::from_secs(config.max_pending_age_secs));
+    let fees = FeeEstimator::new(config.fee_tier, config.base_fee_rate);
+    let mut summary = Summary::default();
+    let mut round = 1;
+    loop {
+        rpc::refresh_pool(&*client, &own_address, &pool).await?;
+        if let Err(e) = fees.refresh(&*client).await {
+            warn!("No fee estimate from the node, paying BASE_FEE_RATE: {}", e);
+        }
+        let stats = pool.stats();
+        // Merging a single UTXO into ourselves achieves nothing
+        if stats.available == 0 || (to_self && stats.available == 1) {
+            if stats.immature > 0 {
+                println!("⏳ {} immature UTXOs were left untouched", stats.immature);
+            }
+            break;
+        }
+
+        let reserved = pool.reserve_up_to(stats.available);
+        let batches = plan_batches(reserved.clone(), fees.rate(), max_inputs);
+        let unused: Vec<TransactionOutpoint> = reserved
+            .iter()
+            .map(|(outpoint, _)| *outpoint)
+            .filter(|outpoint| !batches.iter().any(|batch| batch.utxos.iter().any(|(o, _)| o == outpoint)))
+            .collect();
+        pool.release(&unused);
+        if batches.is_empty() {
+            println!("⚠️  Remaining UTXOs are too small to pay their own fees");
+            break;
+        }
+        println!("🔁 Round {}: {} transactions", round, batches.len());
+
+        let submitted =
+            submit_batches(&*client, keypair, &destination, to_self, &batches, &pool, budget, &mut summary).await;
+        if summary.budget_exhausted {
+            println!("💸 Fee budget of {} reached, stopping", format_kas(config.max_total_fees_sompi));
+            break;
+        }
+        if sweep || !to_self || batches.len() == 1 || submitted == 0 {
+            if stats.immature > 0 {
+                println!("⏳ {} immature UTXOs were left untouched", stats.immature);
+            }
+            break;
+        }
+
+        wait_for_outputs(&*client, &own_address, &pool, config).await?;
+        round += 1;
+    }
+
+    println!();
+    println!("✅ Done");
+    println!("   Transactions:  {}", summary.transactions);
+    println!("   Inputs merged: {}", summary.inputs);
+    println!("   Amount moved:  {}", format_kas(summary.amount));
+    println!("   Fees paid:     {}", budget.describe());
+    Ok(())
+}
+
+/// `--sweep-wallets FILE --to ADDRESS` sends every spendable UTXO of every wallet in a
+/// `--gen-wallets` file to `ADDRESS`, signing with each wallet's own key.
+pub async fn sweep_wallets(config: &Config, path: &str, args: &[String]) -> Result<(), Box<dyn Error>> {
+    config.require_mainnet_confirmation()?;
+    let to = cli::arg_value(args, "--to").ok_or("--sweep-wallets needs --to ADDRESS")?;
+    let destination = keys::parse_address(&to, config.network)?;
+    let max_inputs = cli::parse_arg::<usize>(args, "--max-inputs")?
+        .unwrap_or(tx::MAX_INPUTS_PER_TRANSACTION)
+        .clamp(1, tx::MAX_INPUTS_PER_TRANSACTION);
+    let keypairs = wallets::load_keys(path)?;
+    println!("🧹 Sweeping {} wallets from {} to {}", keypairs.len(), path, String::from(&destination));
+
+    let client = rpc::connect(&config.rpc_url, config.wrpc_encoding).await?;
+    preflight::check(&*client, config.network).await?;
+    let fees = FeeEstimator::new(config.fee_tier, config.base_fee_rate);
+    if let Err(e) = fees.refresh(&*client).await {
+        warn!("No fee estimate from the node, paying BASE_FEE_RATE: {}", e);
+    }
+    let budget = FeeBudget::new(config.max_total_fees_sompi, config.max_fees_per_minute_sompi);
+    let mut total = Summary::default();
+    let mut swept_wallets = 0;
+    for (index, keypair) in keypairs.iter().enumerate() {
+        let address = keys::address_for(keypair, config.network);
+        let pool = UtxoPool::new(config.maturity(), Duration::from_secs(config.max_pending_age_secs));
+        rpc::refresh_pool(&*client, &address, &pool).await?;
+        let stats = pool.stats();
+        let label = format!("   {:>4} {}", index + 1, String::from(&address));
+        let immature = match stats.immature {
+            0 => String::new(),
+            immature => format!(", {} immature UTXOs left", immature),
+        };
+        if stats.available == 0 {
+            println!("{}: nothing to sweep{}", label, immature);
+            continue;
+        }
+        let batches = plan_batches(pool.reserve_up_to(stats.available), fees.rate(), max_inputs);
+        if batches.is_empty() {
+            println!("{}: {} UTXOs too small to pay their own fees{}", label, stats.available, immature);
+            continue;
+        }
+        let mut summary = Summary::default();
+        submit_batches(&*client, *keypair, &destination, false, &batches, &pool, &budget, &mut summary).await;
+        println!(
+            "{}: swept {} from {} UTXOs in {} transactions, fees {}{}",
+            label,
+            format_kas(summary.amount),
+            summary.inputs,
+            summary.transactions,
+            format_kas(summary.fees),
+            immature
+        );
+        if summary.transactions > 0 {
+            swept_wallets += 1;
+        }
+        total.transactions += summary.transactions;
+        total.inputs += summary.inputs;
+        total.amount += summary.amount;
+        total.fees += summary.fees;
+        if summary.budget_exhausted {
+            println!("💸 Fee budget of {} reached, stopping", format_kas(config.max_total_fees_sompi));
+            break;
+        }
+    }
+
+    println!();
+    println!("✅ Done");
+    println!("   Wallets swept: {} of {}", swept_wallets, keypairs.len());
+    println!("   Transactions:  {}", total.transactions);
+    println!("   Inputs swept:  {}", total.inputs);
+    println!("   Amount moved:  {}", format_kas(total.amount));
+    println!("   Fees paid:     {}", budget.describe());
+    Ok(())
+}
+
+/// Greedily packs UTXOs into transactions that stay within the input count and
+/// standard mass limits. Batches whose inputs cannot cover their fee are dropped.
+fn plan_batches(utxos: Vec<Utxo>, fee_rate: u64, max_inputs: usize) -> Vec<Batch> {
+    let mut batches = Vec::new();
+    let mut current: Vec<Utxo> = Vec::new();
+    for utxo in utxos {
+        let fits = current.len() < max_inputs
+            && tx::compute_mass(current.len() + 1, 1, 0) <= tx::MAXIMUM_STANDARD_TRANSACTION_MASS;
+        if !fits {
+            batches.extend(close_batch(std::mem::take(&mut current), fee_rate));
+        }
+        current.push(utxo);
+    }
+    batches.extend(close_batch(current, fee_rate));
+    batches
+}
+
+fn close_batch(utxos: Vec<Utxo>, fee_rate: u64) -> Option<Batch> {
+    if utxos.is_empty() {
+        return None;
+    }
+    let input_amounts: Vec<u64> = utxos.iter().map(|(_, entry)| entry.amount).collect();
+    let total: u64 = input_amounts.iter().sum();
+    // Storage mass depends on the output value, so price the compute mass first
+    let compute_fee = tx::required_fee(fee_rate, tx::compute_mass(utxos.len(), 1, 0));
+    let mass = tx::estimated_mass(&input_amounts, &[total.saturating_sub(compute_fee).max(1)], 0);
+    let fee = tx::required_fee(fee_rate, mass);
+    if mass > tx::MAXIMUM_STANDARD_TRANSACTION_MASS || fee >= total {
+        return None;
+    }
+    Some(Batch { output_amount: total - fee, fee, utxos })
+}
+
+/// Submits every batch, marking accepted inputs as spent in `pool` and releasing
+/// the inputs of rejected transactions. Stops early once the fee budget is spent.
+/// Returns how many were accepted.
+#[allow(clippy::too_many_arguments)]
+async fn submit_batches(
+    client: &dyn RpcBackend,
+    keypair: Keypair,
+    destination: &Address,
+    to_self: bool,
+    batches: &[Batch],
+    pool: &UtxoPool,
+    budget: &FeeBudget,
+    summary: &mut Summary,
+) -> usize {
+    let script_public_key = pay_to_address_script(destination);
+    let mut submitted = 0;
+    for (index, batch) in batches.iter().enumerate() {
+        let Ok(grant) = budget.spend(batch.fee).await else {
+            let unsent: Vec<TransactionOutpoint> =
+                batches[index..].iter().flat_map(|batch| batch.utxos.iter().map(|(outpoint, _)| *outpoint)).collect();
+            pool.release(&unsent);
+            summary.budget_exhausted = true;
+            break;
+        };
+        let outputs = vec![TransactionOutput::new(batch.output_amount, script_public_key.clone())];
+        let tx = tx::build_signed_tx(keypair, &batch.utxos, outputs, vec![]);
+        let outpoints: Vec<TransactionOutpoint> = batch.utxos.iter().map(|(outpoint, _)| *outpoint).collect();
+        match rpc::submit_transaction(client, &tx).await {
+            Ok(txid) => {
+                info!("Submitted {} merging {} inputs into {}", txid, batch.utxos.len(), format_kas(batch.output_amount));
+                let change = if to_self {
+                    let entry = UtxoEntry::new(batch.output_amount, script_public_key.clone(), 0, false);
+                    vec![(TransactionOutpoint::new(txid, 0), entry)]
+                } else {
+                    vec![]
+                };
+                pool.mark_submitted(txid, &outpoints, change);
+                summary.transactions += 1;
+                summary.inputs += batch.utxos.len();
+                summary.amount += batch.output_amount;
+                summary.fees += batch.fee;
+                submitted += 1;
+            }
+            Err(e) => {
+                warn!("Failed to submit consolidation transaction {}: {}", tx.id(), e);
+                budget.refund(grant);
+                pool.release(&outpoints);
+            }
+        }
+    }
+    submitted
+}
+
+/// Waits until the outputs of the last round are spendable.
+async fn wait_for_outputs(
+    client: &dyn RpcBackend,
+    address: &Address,
+    pool: &UtxoPool,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let started = tokio::time::Instant::now();
+    loop {
+        tokio::time::sleep(Duration::from_secs(config.utxo_refresh_secs.max(1))).await;
+        rpc::refresh_pool(client, address, pool).await?;
+        let pruned = pool.prune_stale() + rpc::release_abandoned(client, pool).await?;
+        if pruned > 0 {
+            warn!("Gave up on {} consolidation outputs older than {}s", pruned, config.max_pending_age_secs);
+        }
+        let stats = pool.stats();
+        if stats.pending_change == 0 {
+            return Ok(());
+        }
+        if started.elapsed() > ROUND_CONFIRMATION_TIMEOUT {
+            return Err(format!(
+                "{} consolidation outputs did not confirm in time ({} inputs still reserved)",
+                stats.pending_change, stats.reserved
+            )
+            .into());
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fs, str::FromStr};
+
+use rand::Rng;
+
+/// A distribution to sample per-transaction values from, parsed from a spec:
+///
+/// - `fixed:V`
+/// - `uniform:MIN,MAX`
+/// - `normal:MEAN,STD_DEV` (negative samples are clamped to 0)
+/// - `buckets:V1=W1,V2=W2,...` (value V with relative weight W)
+/// - `file:PATH`, an empirical histogram with one `value,count` row per bin
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    Fixed(f64),
+    Uniform { min: f64, max: f64 },
+    Normal { mean: f64, std_dev: f64 },
+    /// Values with relative weights; also used for histogram files
+    Weighted(Vec<(f64, f64)>),
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+        let numbers = || -> Result<Vec<f64>, String> {
+            params
+                .split(',')
+                .map(|value| value.trim().parse::<f64>().map_err(|e| format!("invalid number {}: {}", value, e)))
+                .collect()
+        };
+        let distribution = match (kind.trim().to_lowercase().as_str(), numbers()) {
+            ("fixed", Ok(values)) if values.len() == 1 => Distribution::Fixed(values[0]),
+            ("uniform", Ok(values)) if values.len() == 2 && values[0] <= values[1] => {
+                Distribution::Uniform { min: values[0], max: values[1] }
+            }
+            ("normal", Ok(values)) if values.len() == 2 && values[1] >= 0.0 => {
+                Distribution::Normal { mean: values[0], std_dev: values[1] }
+            }
+            ("buckets", _) => Distribution::Weighted(parse_buckets(params)?),
+            ("file", _) => {
+                let contents =
+                    fs::read_to_string(params).map_err(|e| format!("cannot read histogram {}: {}", params, e))?;
+                Distribution::Weighted(parse_histogram(&contents)?)
+            }
+            _ => {
+                return Err(format!(
+                    "invalid distribution {}. Expected fixed:V, uniform:MIN,MAX, normal:MEAN,STD_DEV, \
+                     buckets:V=W,... or file:PATH",
+                    s
+                ))
+            }
+        };
+        let valid = |value: f64| value.is_finite() && value >= 0.0;
+        let valid = match &distribution {
+            Distribution::Fixed(value) => valid(*value),
+            Distribution::Uniform { min, max } => valid(*min) && valid(*max),
+            Distribution::Normal { mean, std_dev } => mean.is_finite() && std_dev.is_finite(),
+            Distribution::Weighted(buckets) => buckets.iter().all(|(value, _)| valid(*value)),
+        };
+        if !valid {
+            return Err(format!("distribution {} has a negative or non-finite value", s));
+        }
+        Ok(distribution)
+    }
+}
+
+fn parse_buckets(params: &str) -> Result<Vec<(f64, f64)>, String> {
+    let buckets = params
+        .split(',')
+        .map(|bucket| {
+            let (value, weight) = bucket.split_once('=').ok_or_else(|| format!("bucket {} is not V=W", bucket))?;
+            let value = value.trim().parse::<f64>().map_err(|e| format!("invalid bucket value {}: {}", value, e))?;
+            let weight = weight.trim().parse::<f64>().map_err(|e| format!("invalid bucket weight {}: {}", weight, e))?;
+            Ok((value, weight))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    check_weights(buckets)
+}
+
+/// Reads `value,count` rows; a header or comment rows that do not parse are skipped.
+fn parse_histogram(contents: &str) -> Result<Vec<(f64, f64)>, String> {
+    let bins = contents
+        .lines()
+        .filter_map(|line| {
+            let (value, count) = line.split_once(',')?;
+            Some((value.trim().parse::<f64>().ok()?, count.trim().parse::<f64>().ok()?))
+        })
+        .collect();
+    check_weights(bins)
+}
+
+// ----------------------- weights -----------------------
+/// Checks the weights of `entries`, e.g. distribution buckets or a shape mix.
+pub fn check_weights<T>(entries: Vec<(T, f64)>) -> Result<Vec<(T, f64)>, String> {
+    if entries.iter().any(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+        || entries.iter().map(|(_, weight)| weight).sum::<f64>() <= 0.0
+    {
+        return Err("weights must be finite, non-negative and not all zero".to_string());
+    }
+    Ok(entries)
+}
+
+/// Picks an entry with probability proportional to its weight.
+pub fn choose_weighted<'a, T, R: Rng>(entries: &'a [(T, f64)], rng: &mut R) -> Option<&'a T> {
+    let total: f64 = entries.iter().map(|(_, weight)| weight).sum();
+    let mut target = rng.gen::<f64>() * total;
+    for (entry, weight) in entries {
+        if target < *weight {
+            return Some(entry);
+        }
+        target -= weight;
+    }
+    entries.last().map(|(entry, _)| entry)
+}
+
+impl Distribution {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self {
+            Distribution::Fixed(value) => *value,
+            Distribution::Uniform { min, max } => min + (max - min) * rng.gen::<f64>(),
+            Distribution::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let (u1, u2) = (1.0 - rng.gen::<f64>(), rng.gen::<f64>());
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                (mean + std_dev * z).max(0.0)
+            }
+            Distribution::Weighted(buckets) => choose_weighted(buckets, rng).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Expected value, for planning.
+    pub fn mean(&self) -> f64 {
+        match self {
+            Distribution::Fixed(value) => *value,
+            Distribution::Uniform { min, max } => (min + max) / 2.0,
+            Distribution::Normal { mean, .. } => mean.max(0.0),
+            Distribution::Weighted(buckets) => {
+                let total: f64 = buckets.iter().map(|(_, weight)| weight).sum();
+                buckets.iter().map(|(value, weight)| value * weight).sum::<f64>() / total
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const SAMPLES: usize = 20_000;
+
+    fn samples(spec: &str) -> Vec<f64> {
+        let distribution: Distribution = spec.parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..SAMPLES).map(|_| distribution.sample(&mut rng)).collect()
+    }
+
+    fn average(samples: &[f64]) -> f64 {
+        samples.iter().sum::<f64>() / samples.len() as f64
+    }
+
+    #[test]
+    fn rejects_negative_and_non_finite_values() {
+        let invalid = [
+            "fixed:-1",
+            "fixed:NaN",
+            "uniform:-1,5",
+            "uniform:0,inf",
+            "uniform:5,1",
+            "normal:0,inf",
+            "buckets:-1=1",
+            "buckets:1=inf",
+            "buckets:1=0",
+        ];
+        for spec in invalid {
+            assert!(spec.parse::<Distribution>().is_err(), "{} parsed", spec);
+        }
+        let path = std::env::temp_dir().join(format!("distribution-histogram-{}.csv", std::process::id()));
+        fs::write(&path, "value,count\n-5,1\n").unwrap();
+        let histogram = format!("file:{}", path.display()).parse::<Distribution>();
+        fs::remove_file(&path).unwrap();
+        assert!(histogram.is_err());
+    }
+
+    #[test]
+    fn samples_stay_within_the_distribution() {
+        assert!(samples("fixed:250").iter().all(|&value| value == 250.0));
+        let uniform = samples("uniform:100,200");
+        assert!(uniform.iter().all(|&value| (100.0..200.0).contains(&value)));
+        assert!((average(&uniform) - 150.0).abs() < 2.0);
+        let normal = samples("normal:5,10");
+        assert!(normal.iter().all(|&value| value >= 0.0));
+        assert!((average(&samples("normal:100,10")) - 100.0).abs() < 1.0);
+
+        let weighted = samples("buckets:1=1,2=3,3=0");
+        assert!(weighted.iter().all(|&value| value == 1.0 || value == 2.0));
+        let twos = weighted.iter().filter(|&&value| value == 2.0).count() as f64 / SAMPLES as f64;
+        assert!((twos - 0.75).abs() < 0.02, "{} of the samples were 2", twos);
+    }
+
+    #[test]
+    fn mean_matches_the_spec() {
+        let mean = |spec: &str| spec.parse::<Distribution>().unwrap().mean();
+        assert_eq!(mean("fixed:5"), 5.0);
+        assert_eq!(mean("uniform:2,4"), 3.0);
+        assert_eq!(mean("normal:-5,1"), 0.0);
+        assert_eq!(mean("buckets:1=1,3=3"), 2.5);
+        assert_eq!(Distribution::Weighted(parse_histogram("10,1\n20,1\n").unwrap()).mean(), 15.0);
+    }
+}
@@ -0,0 +1,224 @@
+use std::{error::Error, time::Duration};
+
+use crate::{
+    cli,
+    config::Config,
+    fees::FeeEstimator,
+    keys, preflight,
+    profile::Curve,
+    rpc, scheduler,
+    tx::{self, format_kas},
+    utxo::{self, Utxo},
+};
+
+/// Length of the simulated pacing run.
+const PACING_SIMULATION: Duration = Duration::from_secs(60);
+
+/// Outcome of simulating the split phase from the largest spendable UTXO.
+struct SplitPlan {
+    transactions: usize,
+    outputs_created: usize,
+    total_fees: u64,
+    max_mass: u64,
+}
+
+/// `--dry-run`: reports what a run with the current configuration would cost
+/// without building or submitting anything.
+pub async fn run(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    println!("🧪 Dry run: nothing will be submitted");
+    println!();
+
+    let fees = FeeEstimator::new(config.fee_tier, config.base_fee_rate);
+    let (utxos, virtual_daa_score) = match cli::arg_value(args, "--utxo-snapshot") {
+        Some(filename) => {
+            println!("📂 UTXO source: snapshot {}", filename);
+            let (utxos, virtual_daa_score) = utxo::load_snapshot(&filename)?;
+            if virtual_daa_score.is_none() {
+                println!("⚠️  Snapshot has no virtual DAA score; treating every UTXO as mature");
+            }
+            (utxos, virtual_daa_score)
+        }
+        None => {
+            let keypair = keys::keypair_from_hex(config.private_key_hex()?)?;
+            let address = keys::address_for(&keypair, config.network);
+            println!("🌐 UTXO source: {} for {}", config.rpc_url, String::from(&address));
+            let client = rpc::connect(&config.rpc_url, config.wrpc_encoding).await?;
+            preflight::check(&*client, config.network).await?;
+            if let Err(e) = fees.refresh(&*client).await {
+                println!("⚠️  No fee estimate from the node, planning with BASE_FEE_RATE: {}", e);
+            }
+            let utxos = rpc::fetch_utxos(&*client, &address).await?;
+            let virtual_daa_score = rpc::virtual_daa_score(&*client).await?;
+            if let Some(filename) = cli::arg_value(args, "--save-snapshot") {
+                utxo::save_snapshot(&utxos, virtual_daa_score, &filename)?;
+                println!("💾 Saved {} UTXOs to {}", utxos.len(), filename);
+            }
+            (utxos, Some(virtual_daa_score))
+        }
+    };
+    // The maturity rule of the live pool: coinbase outputs wait COINBASE_MATURITY,
+    // others NON_COINBASE_CONFIRMATIONS
+    let (spendable, immature): (Vec<Utxo>, Vec<Utxo>) =
+        utxos.into_iter().partition(|(_, entry)| match virtual_daa_score {
+            Some(score) => utxo::is_utxo_spendable(entry, score, config.maturity()),
+            None => true,
+        });
+    let immature = immature.len();
+    let confirmation_secs =
+        cli::parse_arg::<u64>(args, "--confirmation-secs")?.unwrap_or(config.expected_confirmation_secs);
+
+    print_report(config, &spendable, immature, confirmation_secs, fees.rate());
+    Ok(())
+}
+
+fn print_report(config: &Config, spendable: &[Utxo], immature: usize, confirmation_secs: u64, fee_rate: u64) {
+    let balance: u64 = spendable.iter().map(|(_, entry)| entry.amount).sum();
+    let largest = spendable.iter().map(|(_, entry)| entry.amount).max().unwrap_or(0);
+
+    println!("📊 Current UTXOs");
+    println!("   Spendable:          {} ({})", spendable.len(), format_kas(balance));
+    println!("   Immature:           {}", immature);
+    println!("   Largest:            {}", format_kas(largest));
+    println!();
+
+    // Split phase
+    let missing = config.target_utxo_count.saturating_sub(spendable.len());
+    let split = plan_split(config, largest, missing, fee_rate);
+    println!("✂️  Split phase");
+    println!("   Target UTXO count:  {}", config.target_utxo_count);
+    println!("   Missing UTXOs:      {}", missing);
+    println!("   Split transactions: {} ({} outputs each)", split.transactions, config.outputs_per_transaction);
+    println!("   Split fees:         {}", format_kas(split.total_fees));
+    if split.outputs_created < missing {
+        println!(
+            "   ⚠️  Largest UTXO runs out after {} outputs; {} UTXOs would still be missing",
+            split.outputs_created,
+            missing - split.outputs_created
+        );
+    }
+    if split.max_mass > tx::MAXIMUM_STANDARD_TRANSACTION_MASS {
+        println!(
+            "   ⚠️  Split transactions reach mass {} (limit {}); reduce OUTPUTS_PER_TRANSACTION or raise AMOUNT_PER_UTXO",
+            split.max_mass,
+            tx::MAXIMUM_STANDARD_TRANSACTION_MASS
+        );
+    }
+    println!();
+
+    // Spam phase
+    let profile = config.traffic_profile();
+    // An indefinite run is planned per hour
+    let planned = Duration::from_secs(match config.spam_duration_seconds {
+        0 => 3600,
+        secs => secs,
+    });
+    let mean_tps = profile.mean_tps(planned);
+    let peak_tps = profile.peak_tps().ceil() as u64;
+    // With distributions configured, plan with their means
+    let payload_len = config.payload_size_distribution.as_ref().map_or(0, |sizes| sizes.mean().round() as usize);
+    let shapes = &config.tx_shapes;
+    let mass = shapes
+        .mean_by(|shape| tx::compute_mass(shape.inputs, shape.outputs, shape.payload_len(payload_len)) as f64)
+        .round() as u64;
+    let inputs_per_tx = shapes.mean_by(|shape| shape.inputs as f64);
+    let (fee_rate, rate_source) = match &config.fee_rate_distribution {
+        Some(rates) => (rates.mean().max(config.base_fee_rate as f64), "FEE_RATE_DIST mean".to_string()),
+        None => (fee_rate as f64, format!("{} tier", config.fee_tier)),
+    };
+    let fee_per_tx = tx::fee_at_rate(fee_rate, mass);
+    let spam_txs = (mean_tps * planned.as_secs_f64()).round() as u64;
+    let spam_fees = fee_per_tx * spam_txs;
+    println!("🚀 Spam phase");
+    if profile.is_capped() {
+        println!("   Target TPS:         {} (UNLEASHED=false)", profile);
+    } else {
+        println!("   Target TPS:         {}", profile);
+    }
+    if !matches!(profile.curve(), Curve::Constant(_)) {
+        println!("   Mean / peak TPS:    {:.1} / {}", mean_tps, peak_tps);
+    }
+    println!(
+        "   Fee per tx:         {} (mass {}, {:.2} sompi/gram, {})",
+        format_kas(fee_per_tx),
+        mass,
+        fee_rate,
+        rate_source
+    );
+    if !shapes.is_default() {
+        println!("   Shapes:             {} ({:.1} inputs per tx on average)", shapes, inputs_per_tx);
+    }
+    let simulated = scheduler::simulate(&profile, Duration::from_millis(config.millis_per_tick), PACING_SIMULATION);
+    println!(
+        "   Simulated pacing:   {} tx in {}s ({:.2} TPS, {}ms ticks)",
+        simulated,
+        PACING_SIMULATION.as_secs(),
+        simulated as f64 / PACING_SIMULATION.as_secs_f64(),
+        config.millis_per_tick.max(1)
+    );
+    if config.spam_duration_seconds == 0 {
+        println!("   Duration:           indefinite");
+        println!("   Fees per hour:      {}", format_kas(spam_fees));
+    } else {
+        println!("   Duration:           {}s", config.spam_duration_seconds);
+        println!("   Transactions:       {}", spam_txs);
+        println!("   Spam fees:          {}", format_kas(spam_fees));
+    }
+    println!();
+
+    // Funding
+    let pool_size = spendable.len().max(config.target_utxo_count);
+    let pool_value = config.amount_per_utxo * config.target_utxo_count as u64;
+    let required = pool_value + split.total_fees + spam_fees;
+    println!("💰 Funding");
+    println!("   Required balance:   {}", format_kas(required));
+    println!("   Available balance:  {}", format_kas(balance));
+    if balance >= required {
+        println!("   ✅ Balance covers the planned run");
+    } else {
+        println!("   ❌ Short by {}", format_kas(required - balance));
+    }
+    if pool_size > 0 && spam_txs > 0 {
+        let drain_per_utxo = fee_per_tx * spam_txs.div_ceil(pool_size as u64);
+        if drain_per_utxo + config.min_change_sompi > config.amount_per_utxo {
+            println!(
+                "   ⚠️  Each UTXO pays about {} in fees and will drop below MIN_CHANGE_SOMPI",
+                format_kas(drain_per_utxo)
+            );
+        }
+    }
+    println!();
+
+    // Sustainability
+    let cycle_secs = (confirmation_secs + config.utxo_refresh_secs).max(1);
+    let sustainable_tps = (pool_size as f64 / cycle_secs as f64 / inputs_per_tx) as u64;
+    let needed_utxos = (peak_tps as f64 * cycle_secs as f64 * inputs_per_tx).ceil() as u64;
+    println!("⏱️  Sustainability");
+    println!("   UTXO reuse cycle:   {}s ({}s confirmation + {}s refresh)", cycle_secs, confirmation_secs, config.utxo_refresh_secs);
+    println!("   Pool size:          {} UTXOs", pool_size);
+    println!("   Sustainable TPS:    {}", sustainable_tps);
+    if sustainable_tps >= peak_tps {
+        println!("   ✅ Pool can sustain {} TPS", peak_tps);
+    } else {
+        println!("   ❌ Pool cannot sustain {} TPS; raise TARGET_UTXO_COUNT to at least {}", peak_tps, needed_utxos);
+    }
+}
+
+fn plan_split(config: &Config, largest: u64, missing: usize, fee_rate: u64) -> SplitPlan {
+    let mut plan = SplitPlan { transactions: 0, outputs_created: 0, total_fees: 0, max_mass: 0 };
+    let per_tx = config.outputs_per_transaction.max(1);
+    let mut remaining = largest;
+    while plan.outputs_created < missing {
+        let outputs = per_tx.min(missing - plan.outputs_created);
+        let fee = tx::required_fee_splitting(fee_rate, remaining, config.amount_per_utxo, outputs);
+        let spent = config.amount_per_utxo * outputs as u64 + fee;
+        if spent > remaining {
+            break;
+        }
+        plan.max_mass = plan.max_mass.max(tx::splitting_mass(remaining, config.amount_per_utxo, outputs));
+        plan.transactions += 1;
+        plan.outputs_created += outputs;
+        plan.total_fees += fee;
+        remaining -= spent;
+    }
+    plan
+}
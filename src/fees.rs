@@ -0,0 +1,145 @@
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use kaspa_rpc_core::RpcFeeEstimate;
+use log::{info, warn};
+
+use crate::{backend::RpcBackend, client_pool::ClientPool};
+
+// ----------------------- tiers -----------------------
+/// Which of the node's fee estimate buckets to pay (`FEE_TIER`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeTier {
+    /// Always pay `BASE_FEE_RATE`, never query the node
+    Static,
+    Priority,
+    Normal,
+    Low,
+    /// Percentile of all bucket fee rates, 0 = cheapest, 100 = priority
+    Percentile(f64),
+}
+
+impl FromStr for FeeTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "static" => Ok(FeeTier::Static),
+            "priority" => Ok(FeeTier::Priority),
+            "normal" => Ok(FeeTier::Normal),
+            "low" => Ok(FeeTier::Low),
+            _ => match s.strip_prefix('p').and_then(|p| p.parse::<f64>().ok()) {
+                Some(percentile) if (0.0..=100.0).contains(&percentile) => Ok(FeeTier::Percentile(percentile)),
+                _ => Err(format!("Unknown fee tier: {}. Supported: static, priority, normal, low, p0-p100", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for FeeTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeeTier::Static => write!(f, "static"),
+            FeeTier::Priority => write!(f, "priority"),
+            FeeTier::Normal => write!(f, "normal"),
+            FeeTier::Low => write!(f, "low"),
+            FeeTier::Percentile(percentile) => write!(f, "p{}", percentile),
+        }
+    }
+}
+
+impl FeeTier {
+    /// Fee rate of this tier in `estimate`, in sompi per gram.
+    pub fn select(&self, estimate: &RpcFeeEstimate) -> Option<f64> {
+        let priority = estimate.priority_bucket.feerate;
+        let normal = estimate.normal_buckets.first().map(|bucket| bucket.feerate);
+        let low = estimate.low_buckets.first().map(|bucket| bucket.feerate);
+        match self {
+            FeeTier::Static => None,
+            FeeTier::Priority => Some(priority),
+            FeeTier::Normal => Some(normal.unwrap_or(priority)),
+            FeeTier::Low => Some(low.or(normal).unwrap_or(priority)),
+            FeeTier::Percentile(percentile) => {
+                let mut rates: Vec<f64> = std::iter::once(&estimate.priority_bucket)
+                    .chain(&estimate.normal_buckets)
+                    .chain(&estimate.low_buckets)
+                    .map(|bucket| bucket.feerate)
+                    .collect();
+                rates.sort_by(|a, b| a.total_cmp(b));
+                let index = (percentile / 100.0 * (rates.len() - 1) as f64).round() as usize;
+                Some(rates[index])
+            }
+        }
+    }
+}
+
+// ----------------------- estimator -----------------------
+/// Current fee rate for new transactions: the selected tier of the node's latest
+/// fee estimate, never below `BASE_FEE_RATE`.
+pub struct FeeEstimator {
+    tier: FeeTier,
+    floor: u64,
+    rate: AtomicU64,
+}
+
+impl FeeEstimator {
+    pub fn new(tier: FeeTier, floor: u64) -> Self {
+        FeeEstimator { tier, floor, rate: AtomicU64::new(floor) }
+    }
+
+    /// Fee rate in sompi per gram.
+    pub fn rate(&self) -> u64 {
+        self.rate.load(Ordering::Relaxed)
+    }
+
+    pub fn tier(&self) -> FeeTier {
+        self.tier
+    }
+
+    /// Applies the tier to `estimate` and logs the change, if any.
+    pub fn apply(&self, estimate: &RpcFeeEstimate) {
+        let Some(feerate) = self.tier.select(estimate) else {
+            return;
+        };
+        let rate = (feerate.ceil() as u64).max(self.floor);
+        let previous = self.rate.swap(rate, Ordering::Relaxed);
+        if previous != rate {
+            info!(
+                "Fee rate {} -> {} sompi/gram ({} tier, node estimate {:.2}, floor {})",
+                previous, rate, self.tier, feerate, self.floor
+            );
+        }
+    }
+
+    /// Queries the node's fee estimate. A no-op for the static tier.
+    pub async fn refresh(&self, client: &dyn RpcBackend) -> Result<(), Box<dyn Error>> {
+        if self.tier == FeeTier::Static {
+            return Ok(());
+        }
+        let estimate = client.api().get_fee_estimate().await?;
+        self.apply(&estimate);
+        Ok(())
+    }
+}
+
+/// Refreshes `estimator` from the node every `interval`.
+pub async fn follow(clients: Arc<ClientPool>, estimator: Arc<FeeEstimator>, interval: Duration) {
+    if estimator.tier() == FeeTier::Static {
+        return;
+    }
+    loop {
+        tokio::time::sleep(interval).await;
+        let estimator = &estimator;
+        if let Err(e) = clients.call(|client| async move { estimator.refresh(&*client).await }).await {
+            warn!("Failed to refresh the fee estimate: {}", e);
+        }
+    }
+}
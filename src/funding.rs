@@ -0,0 +1,325 @@
+use std::{collections::HashSet, error::Error, path::Path, time::Duration};
+
+use kaspa_addresses::Address;
+use kaspa_consensus_core::{
+    constants::SOMPI_PER_KASPA,
+    tx::{TransactionOutpoint, TransactionOutput, UtxoEntry},
+};
+use kaspa_txscript::pay_to_address_script;
+use log::{info, warn};
+use secp256k1::Keypair;
+use serde::Serialize;
+
+use crate::{
+    backend::RpcBackend,
+    budget::FeeBudget,
+    cli,
+    config::Config,
+    fees::FeeEstimator,
+    keys, preflight, rpc,
+    tx::{self, format_kas},
+    utxo::{Utxo, UtxoPool},
+    wallets,
+};
+
+/// How long to wait for the funding transactions to be accepted before giving up.
+const ACCEPTANCE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// One funding transaction: `inputs` pay `amount` to `recipients` consecutive
+/// wallets, plus change back to the master key.
+struct Batch {
+    inputs: Vec<Utxo>,
+    recipients: usize,
+    fee: u64,
+    change: u64,
+}
+
+/// Fee, change and mass of a funding transaction for a given input set.
+struct Priced {
+    fee: u64,
+    change: u64,
+    mass: u64,
+}
+
+/// One row of the funding report.
+#[derive(Debug, Serialize)]
+struct FundingRecord {
+    wallet: usize,
+    address: String,
+    amount_sompi: u64,
+    txid: String,
+    /// "accepted", "pending", "skipped" or the node's rejection message
+    status: String,
+}
+
+/// `--fund-wallets FILE --amount KAS [--report FILE]` pays every wallet of a
+/// `--gen-wallets` file `--amount` KAS from the master key, in as few mass-limited
+/// transactions as possible, waits until the node accepted them and writes a CSV
+/// report with the txid per address.
+pub async fn run(config: &Config, path: &str, args: &[String]) -> Result<(), Box<dyn Error>> {
+    config.require_mainnet_confirmation()?;
+    let amount_kas: f64 = cli::parse_arg(args, "--amount")?.ok_or("--fund-wallets needs --amount KAS")?;
+    let amount = (amount_kas * SOMPI_PER_KASPA as f64).round() as u64;
+    if amount == 0 {
+        return Err("--amount must be positive".into());
+    }
+    let report_path = cli::arg_value(args, "--report").unwrap_or_else(|| default_report_path(path));
+    let keypair = keys::keypair_from_hex(config.private_key_hex()?)?;
+    let own_address = keys::address_for(&keypair, config.network);
+    let recipients: Vec<Address> =
+        wallets::load_keys(path)?.iter().map(|wallet| keys::address_for(wallet, config.network)).collect();
+    println!(
+        "💸 Funding {} wallets from {} with {} each",
+        recipients.len(),
+        String::from(&own_address),
+        format_kas(amount)
+    );
+
+    let client = rpc::connect(&config.rpc_url, config.wrpc_encoding).await?;
+    preflight::check(&*client, config.network).await?;
+    let pool = UtxoPool::new(config.maturity(), Duration::from_secs(config.max_pending_age_secs));
+    rpc::refresh_pool(&*client, &own_address, &pool).await?;
+    let fees = FeeEstimator::new(config.fee_tier, config.base_fee_rate);
+    if let Err(e) = fees.refresh(&*client).await {
+        warn!("No fee estimate from the node, paying BASE_FEE_RATE: {}", e);
+    }
+    let budget = FeeBudget::new(config.max_total_fees_sompi, config.max_fees_per_minute_sompi);
+
+    let available = pool.reserve_up_to(usize::MAX);
+    let (batches, unused) = plan_batches(available, recipients.len(), amount, fees.rate(), config.min_change_sompi)?;
+    pool.release(&unused.iter().map(|(outpoint, _)| *outpoint).collect::<Vec<_>>());
+    let funded: usize = batches.iter().map(|batch| batch.recipients).sum();
+    if funded < recipients.len() {
+        warn!("Spendable balance covers only {} of {} wallets", funded, recipients.len());
+    }
+    println!("📦 {} transactions for {} wallets", batches.len(), funded);
+
+    let mut records: Vec<FundingRecord> = recipients
+        .iter()
+        .enumerate()
+        .map(|(index, address)| FundingRecord {
+            wallet: index + 1,
+            address: String::from(address),
+            amount_sompi: amount,
+            txid: String::new(),
+            status: "skipped".to_string(),
+        })
+        .collect();
+    let expected =
+        submit_batches(&*client, keypair, &own_address, &recipients, amount, &batches, &pool, &budget, &mut records)
+            .await;
+    let pending = wait_for_acceptance(&*client, &recipients, expected, config).await?;
+    for (wallet, record) in records.iter_mut().enumerate().filter(|(_, record)| record.status == "submitted") {
+        record.status = if pending.contains(&wallet) { "pending" } else { "accepted" }.to_string();
+    }
+    write_report(&report_path, &records)?;
+
+    let accepted = records.iter().filter(|record| record.status == "accepted").count();
+    let transactions: HashSet<&str> =
+        records.iter().filter(|record| record.status == "accepted").map(|record| record.txid.as_str()).collect();
+    println!();
+    println!("✅ Funded {} of {} wallets", accepted, recipients.len());
+    println!("   Transactions: {}", transactions.len());
+    println!("   Amount sent:  {}", format_kas(amount * accepted as u64));
+    println!("   Fees paid:    {}", budget.describe());
+    println!("   Report:       {}", report_path);
+    if !pending.is_empty() {
+        println!("⏳ {} payments were not accepted within {}s", pending.len(), ACCEPTANCE_TIMEOUT.as_secs());
+    }
+    Ok(())
+}
+
+/// `wallets.csv` → `wallets-funding.csv`, next to the wallet file.
+fn default_report_path(wallet_file: &str) -> String {
+    format!("{}-funding.csv", Path::new(wallet_file).with_extension("").display())
+}
+
+/// Greedily packs payments into transactions, largest UTXOs first: a transaction
+/// takes further recipients while it stays under the standard mass limit, and
+/// further inputs while it cannot pay them. Returns the batches and the UTXOs left
+/// unused.
+fn plan_batches(
+    utxos: Vec<Utxo>,
+    recipients: usize,
+    amount: u64,
+    fee_rate: u64,
+    min_change: u64,
+) -> Result<(Vec<Batch>, Vec<Utxo>), Box<dyn Error>> {
+    let mut utxos = utxos.into_iter();
+    let mut batches = Vec::new();
+    let mut current = Batch { inputs: Vec::new(), recipients: 0, fee: 0, change: 0 };
+    let mut planned = 0;
+    while planned < recipients {
+        let input_amounts: Vec<u64> = current.inputs.iter().map(|(_, entry)| entry.amount).collect();
+        match price(&input_amounts, current.recipients + 1, amount, fee_rate, min_change) {
+            Some(priced) if priced.mass <= tx::MAXIMUM_STANDARD_TRANSACTION_MASS => {
+                current.recipients += 1;
+                current.fee = priced.fee;
+                current.change = priced.change;
+                planned += 1;
+            }
+            Some(_) if current.recipients == 0 => {
+                return Err(format!("A payment of {} exceeds the standard mass limit", format_kas(amount)).into());
+            }
+            Some(_) => {
+                batches.push(std::mem::replace(
+                    &mut current,
+                    Batch { inputs: Vec::new(), recipients: 0, fee: 0, change: 0 },
+                ));
+            }
+            None => {
+                let fits = current.inputs.len() < tx::MAX_INPUTS_PER_TRANSACTION
+                    && tx::compute_mass(current.inputs.len() + 1, current.recipients + 2, 0)
+                        <= tx::MAXIMUM_STANDARD_TRANSACTION_MASS;
+                match utxos.next() {
+                    Some(utxo) if fits => current.inputs.push(utxo),
+                    Some(_) if current.recipients == 0 => {
+                        return Err(format!(
+                            "A payment of {} needs more than {} inputs; consolidate first",
+                            format_kas(amount),
+                            current.inputs.len()
+                        )
+                        .into());
+                    }
+                    Some(utxo) => {
+                        batches.push(std::mem::replace(
+                            &mut current,
+                            Batch { inputs: vec![utxo], recipients: 0, fee: 0, change: 0 },
+                        ));
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+    let mut unused: Vec<Utxo> = utxos.collect();
+    if current.recipients > 0 {
+        batches.push(current);
+    } else {
+        unused.extend(current.inputs);
+    }
+    Ok((batches, unused))
+}
+
+/// Prices a transaction spending `input_amounts` into `recipients` payments of
+/// `amount` plus change, charging the larger of compute and storage mass. Change
+/// below `min_change` is left to the miner. `None` if the inputs cannot pay.
+fn price(input_amounts: &[u64], recipients: usize, amount: u64, fee_rate: u64, min_change: u64) -> Option<Priced> {
+    let total: u64 = input_amounts.iter().sum();
+    let payments = amount.checked_mul(recipients as u64)?;
+    let mut fee = tx::required_fee(fee_rate, tx::compute_mass(input_amounts.len(), recipients + 1, 0));
+    // Storage mass depends on the change, so settle the fee in a few rounds
+    for _ in 0..3 {
+        let change = total.checked_sub(payments + fee)?;
+        let mut outputs = vec![amount; recipients];
+        if change >= min_change {
+            outputs.push(change);
+        }
+        let mass = tx::estimated_mass(input_amounts, &outputs, 0);
+        let required = tx::required_fee(fee_rate, mass);
+        if required <= fee {
+            return Some(if change >= min_change {
+                Priced { fee, change, mass }
+            } else {
+                Priced { fee: fee + change, change: 0, mass }
+            });
+        }
+        fee = required;
+    }
+    None
+}
+
+/// Submits every batch, recording the txid or rejection per wallet. Stops early
+/// once the fee budget is spent. Returns the outpoints expected at the recipients.
+#[allow(clippy::too_many_arguments)]
+async fn submit_batches(
+    client: &dyn RpcBackend,
+    keypair: Keypair,
+    own_address: &Address,
+    recipients: &[Address],
+    amount: u64,
+    batches: &[Batch],
+    pool: &UtxoPool,
+    budget: &FeeBudget,
+    records: &mut [FundingRecord],
+) -> Vec<(usize, TransactionOutpoint)> {
+    let own_script = pay_to_address_script(own_address);
+    let mut expected = Vec::new();
+    let mut first = 0;
+    for (index, batch) in batches.iter().enumerate() {
+        let wallets = first..first + batch.recipients;
+        first += batch.recipients;
+        let Ok(grant) = budget.spend(batch.fee).await else {
+            let unsent: Vec<TransactionOutpoint> =
+                batches[index..].iter().flat_map(|batch| batch.inputs.iter().map(|(outpoint, _)| *outpoint)).collect();
+            pool.release(&unsent);
+            warn!("Fee budget exhausted; {} wallets were not funded", recipients.len() - wallets.start);
+            break;
+        };
+        let mut outputs: Vec<TransactionOutput> = recipients[wallets.clone()]
+            .iter()
+            .map(|address| TransactionOutput::new(amount, pay_to_address_script(address)))
+            .collect();
+        if batch.change > 0 {
+            outputs.push(TransactionOutput::new(batch.change, own_script.clone()));
+        }
+        let tx = tx::build_signed_tx(keypair, &batch.inputs, outputs, vec![]);
+        let outpoints: Vec<TransactionOutpoint> = batch.inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+        match rpc::submit_transaction(client, &tx).await {
+            Ok(txid) => {
+                info!("Submitted {} paying {} wallets, fee {}", txid, batch.recipients, format_kas(batch.fee));
+                let change = match batch.change {
+                    0 => vec![],
+                    change => {
+                        let entry = UtxoEntry::new(change, own_script.clone(), 0, false);
+                        vec![(TransactionOutpoint::new(txid, batch.recipients as u32), entry)]
+                    }
+                };
+                pool.mark_submitted(txid, &outpoints, change);
+                for (output, wallet) in wallets.enumerate() {
+                    records[wallet].txid = txid.to_string();
+                    records[wallet].status = "submitted".to_string();
+                    expected.push((wallet, TransactionOutpoint::new(txid, output as u32)));
+                }
+            }
+            Err(e) => {
+                warn!("Funding transaction {} was rejected: {}", tx.id(), e);
+                budget.refund(grant);
+                pool.release(&outpoints);
+                for wallet in wallets {
+                    records[wallet].txid = tx.id().to_string();
+                    records[wallet].status = e.to_string();
+                }
+            }
+        }
+    }
+    expected
+}
+
+/// Waits until the node reports every expected payment at its recipient. Returns
+/// the wallets whose payment did not show up in time.
+async fn wait_for_acceptance(
+    client: &dyn RpcBackend,
+    recipients: &[Address],
+    mut expected: Vec<(usize, TransactionOutpoint)>,
+    config: &Config,
+) -> Result<HashSet<usize>, Box<dyn Error>> {
+    let started = tokio::time::Instant::now();
+    while !expected.is_empty() && started.elapsed() < ACCEPTANCE_TIMEOUT {
+        tokio::time::sleep(Duration::from_secs(config.utxo_refresh_secs.max(1))).await;
+        let addresses: Vec<Address> = expected.iter().map(|(wallet, _)| recipients[*wallet].clone()).collect();
+        let entries = client.api().get_utxos_by_addresses(addresses).await?;
+        let seen: HashSet<TransactionOutpoint> = entries.into_iter().map(|entry| entry.outpoint.into()).collect();
+        expected.retain(|(_, outpoint)| !seen.contains(outpoint));
+    }
+    Ok(expected.into_iter().map(|(wallet, _)| wallet).collect())
+}
+
+fn write_report(path: &str, records: &[FundingRecord]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| format!("Cannot create {}: {}", path, e))?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
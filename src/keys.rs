@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use kaspa_addresses::{Address, Version};
+use secp256k1::Keypair;
+
+use crate::config::Network;
+
+/// Parses a 32-byte hex private key into a Schnorr keypair.
+pub fn keypair_from_hex(private_key_hex: &str) -> Result<Keypair, Box<dyn Error>> {
+    let bytes = hex::decode(private_key_hex.trim())?;
+    if bytes.len() != 32 {
+        return Err(format!("Private key must be 32 bytes, got {}", bytes.len()).into());
+    }
+    Ok(Keypair::from_seckey_slice(secp256k1::SECP256K1, &bytes)?)
+}
+
+/// Schnorr pay-to-pubkey address of `keypair` on `network`.
+pub fn address_for(keypair: &Keypair, network: Network) -> Address {
+    Address::new(network.address_prefix(), Version::PubKey, &keypair.x_only_public_key().0.serialize())
+}
+
+/// Parses `address` and checks that it belongs to `network`.
+pub fn parse_address(address: &str, network: Network) -> Result<Address, Box<dyn Error>> {
+    let address = Address::try_from(address).map_err(|e| format!("Invalid address {}: {}", address, e))?;
+    if address.prefix != network.address_prefix() {
+        return Err(format!(
+            "Address prefix does not match selected network {}: {} is not a {} address",
+            network,
+            String::from(&address),
+            network.address_prefix()
+        )
+        .into());
+    }
+    Ok(address)
+}
@@ -1,15 +1,57 @@
 use std::{
     env,
-    io::{self, Write},
+    io::Write,
     fs::File,
-    path::Path,
-    sync::Arc,
 };
 
+mod acceptance;
+mod backend;
+mod balances;
+mod budget;
+mod cli;
+mod client_pool;
+mod config;
+mod consolidate;
+mod distribution;
+mod dry_run;
+mod fees;
+mod funding;
+mod keys;
+mod max_tps;
+mod mock_node;
+mod preflight;
+mod profile;
+mod rbf;
+mod rejection;
+mod replenish;
+mod rpc;
+mod scenario;
+mod scheduler;
+mod shape;
+mod spam;
+mod tx;
+mod tx_log;
+mod utxo;
+mod wallets;
+
+use config::Config;
+
 // Wallet generation dependencies
 use blake2::{Blake2b, Digest};
 use serde::{Serialize, Deserialize};
 
+/// Every `--` flag of the commands and options below; anything else is rejected.
+const KNOWN_FLAGS: &[&str] = &[
+    "--spam", "--dry-run", "--balances", "--fund-wallets", "--consolidate", "--sweep-to", "--sweep-wallets",
+    "--find-max-tps", "--scenario", "--rbf", "--mock-node", "--help", "--net", "--i-understand-mainnet",
+    "--utxo-snapshot", "--save-snapshot", "--confirmation-secs", "--json", "--amount", "--report", "--to",
+    "--max-inputs", "--rbf-tests", "--rbf-replacements", "--rbf-delays-ms", "--rbf-start-fee-rate",
+    "--rbf-fee-step", "--start-tps", "--step-tps", "--max-tps", "--precision-tps", "--trial-secs",
+    "--cooldown-secs", "--max-reject-pct", "--max-latency-ms", "--max-lag-secs", "--with-mock-node",
+    "--mock-listen", "--mock-fund", "--mock-fund-sompi", "--mock-fund-utxos", "--mock-latency-ms",
+    "--mock-reject-every", "--mock-reject-kind", "--mock-drop-every", "--mock-bps",
+];
+
 // ----------------------- wallet generation -----------------------
 /// Bech32m charset for Kaspa
 const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
@@ -292,7 +334,26 @@ fn generate_wallets_cli() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Reads the configuration. With `--with-mock-node`, a mock node is started in
+/// this process and the run is pointed at it.
+async fn load_config(args: &[String]) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = Config::from_env(args)?;
+    if cli::has_flag(args, "--with-mock-node") {
+        let mut options = mock_node::MockOptions::from_args(&config, args)?;
+        if cli::arg_value(args, "--mock-listen").is_none() {
+            options.listen = "127.0.0.1:0".to_string();
+        }
+        let local_addr = mock_node::spawn(options).await?;
+        config.rpc_url = format!("ws://{}", local_addr);
+        config.wrpc_encoding = backend::Encoding::Json;
+        // Nothing reaches a real network
+        config.mainnet_confirmed = true;
+    }
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logger
     env_logger::init();
 
@@ -301,14 +362,65 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return generate_wallets_cli();
     }
 
-    // Show help if no arguments or help requested
-    if env::args().len() == 1 || env::args().any(|arg| arg == "--help" || arg == "-h") {
+    let args: Vec<String> = env::args().collect();
+    cli::check_flags(&args[1..], KNOWN_FLAGS)?;
+    if cli::has_flag(&args, "--mock-node") {
+        let config = Config::from_env(&args)?;
+        return mock_node::run(&config, &args).await;
+    }
+    if cli::has_flag(&args, "--dry-run") {
+        let config = load_config(&args).await?;
+        return dry_run::run(&config, &args).await;
+    }
+    if cli::has_flag(&args, "--find-max-tps") {
+        let config = load_config(&args).await?;
+        return max_tps::run(&config, &args).await;
+    }
+    if cli::has_flag(&args, "--rbf") {
+        let config = load_config(&args).await?;
+        return rbf::run(&config, &args).await;
+    }
+    if let Some(path) = cli::arg_value(&args, "--scenario") {
+        let config = load_config(&args).await?;
+        return scenario::run(&config, &path).await;
+    }
+    if let Some(target) = cli::arg_value(&args, "--balances") {
+        let config = load_config(&args).await?;
+        return balances::run(&config, &target, &args).await;
+    }
+    if let Some(path) = cli::arg_value(&args, "--fund-wallets") {
+        let config = load_config(&args).await?;
+        return funding::run(&config, &path, &args).await;
+    }
+    if let Some(path) = cli::arg_value(&args, "--sweep-wallets") {
+        let config = load_config(&args).await?;
+        return consolidate::sweep_wallets(&config, &path, &args).await;
+    }
+    if cli::has_flag(&args, "--consolidate") || cli::arg_value(&args, "--sweep-to").is_some() {
+        let config = load_config(&args).await?;
+        return consolidate::run(&config, &args).await;
+    }
+
+    // Show help if requested
+    if env::args().any(|arg| arg == "--help" || arg == "-h") {
         println!("🔐 Kaspa Wallet Generator");
         println!("========================");
         println!();
         println!("📋 Commands:");
         println!("   --generate-wallets    Generate 10 wallets in TXT format (default)");
         println!("   --gen-wallets         Same as --generate-wallets");
+        println!("   --dry-run             Estimate split/spam fees and pool sustainability, submit nothing");
+        println!("   --balances TARGET     Show balances of an address or every wallet of a --gen-wallets file");
+        println!("   --fund-wallets FILE   Pay every wallet of a --gen-wallets file --amount KAS from your key");
+        println!("   --consolidate         Merge UTXOs into as few outputs as possible");
+        println!("   --sweep-to ADDRESS    Send every spendable UTXO to ADDRESS");
+        println!("   --sweep-wallets FILE  Send every spendable UTXO of a --gen-wallets file to --to ADDRESS");
+        println!("   --find-max-tps        Raise the rate trial by trial to find the maximum sustained TPS");
+        println!("   --scenario FILE       Run the phases of a TOML scenario file in order");
+        println!("   --rbf                 Send transactions at a low fee, then replace them at rising fees");
+        println!("   --mock-node           Run a simulated node for offline testing (wRPC JSON)");
+        println!("   --spam                Split UTXOs, then send at TARGET_TPS (configured via .env); the default");
+        println!("                         when no command is given");
         println!();
         println!("⚙️  Options:");
         println!("   --count N             Generate N wallets (default: 10)");
@@ -317,6 +429,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("   -f FORMAT             Same as --format FORMAT");
         println!("   --output NAME         Output filename without extension (default: wallets)");
         println!("   -o NAME               Same as --output NAME");
+        println!("   --net NETWORK         Network: mainnet, testnet10 (overrides KASPA_NETWORK)");
+        println!("   --utxo-snapshot FILE  Dry run: plan from a UTXO snapshot CSV instead of the node");
+        println!("   --save-snapshot FILE  Dry run: save the node's UTXOs to a snapshot CSV");
+        println!("   --confirmation-secs N Dry run: expected confirmation time (default: EXPECTED_CONFIRMATION_SECS)");
+        println!("   --json                Balances: print JSON instead of text");
+        println!("   --amount KAS          Fund wallets: amount paid to each wallet");
+        println!("   --report FILE         Fund wallets: CSV report of txids (default: <wallet file>-funding.csv)");
+        println!("                         RBF: CSV report of every version submitted (default: none)");
+        println!("   --rbf-tests N         RBF: transactions to replace (default: 10)");
+        println!("   --rbf-replacements N  RBF: replacements per transaction (default: 3)");
+        println!("   --rbf-delays-ms LIST  RBF: wait before each replacement, the last repeats (default: 1000)");
+        println!("   --rbf-start-fee-rate R RBF: fee rate of the first version (default: BASE_FEE_RATE)");
+        println!("   --rbf-fee-step X      RBF: fee rate factor per replacement (default: 2)");
+        println!("   --to ADDRESS          Consolidate/sweep wallets: destination address (default: own address)");
+        println!("   --max-inputs N        Consolidate/sweep: inputs per transaction (default: 84)");
+        println!("   --start-tps N         Max TPS search: first trial rate (default: 10)");
+        println!("   --step-tps N          Max TPS search: rate added per passing trial (default: double)");
+        println!("   --max-tps N           Max TPS search: highest rate to try (default: 10000)");
+        println!("   --precision-tps N     Max TPS search: stop when the bounds are this close (default: 5)");
+        println!("   --trial-secs N        Max TPS search: length of each trial (default: 30)");
+        println!("   --cooldown-secs N     Max TPS search: back-off after a failed trial (default: 10)");
+        println!("   --max-reject-pct N    Max TPS search: highest rejection rate in percent (default: 1)");
+        println!("   --max-latency-ms N    Max TPS search: highest p95 submit latency (default: 1000)");
+        println!("   --max-lag-secs N      Max TPS search: highest p95 confirmation lag (default: 30)");
+        println!("   --i-understand-mainnet Allow submitting transactions on mainnet");
+        println!("   --with-mock-node      Run any mode against an in-process mock node");
+        println!("   --mock-listen ADDR    Mock node: listen address (default: 127.0.0.1:18110)");
+        println!("   --mock-fund ADDRESS   Mock node: address holding the initial UTXOs (default: own address)");
+        println!("   --mock-fund-sompi N   Mock node: initial balance (default: 1000 KAS)");
+        println!("   --mock-fund-utxos N   Mock node: number of initial UTXOs (default: 1)");
+        println!("   --mock-latency-ms N   Mock node: mempool to block acceptance latency (default: 500)");
+        println!("   --mock-reject-every N Mock node: reject every Nth submitted transaction");
+        println!("   --mock-reject-kind K  Mock node: orphan, double-spend, fee, mass, mempool-full, duplicate, other");
+        println!("   --mock-drop-every N   Mock node: drop the connection instead of answering every Nth submit");
+        println!("   --mock-bps N          Mock node: DAA score increments per second (default: 10)");
         println!("   --help                Show this help");
         println!("   -h                    Same as --help");
         println!();
@@ -325,6 +472,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("   kaspa-tx-generator --gen-wallets --count 5 --format csv");
         println!("   kaspa-tx-generator --gen-wallets -c 20 -f csv -o my_wallets");
         println!("   kaspa-tx-generator --gen-wallets --format=csv --count=100");
+        println!("   kaspa-tx-generator --spam --net tn10");
+        println!("   kaspa-tx-generator --net mainnet --i-understand-mainnet");
+        println!("   kaspa-tx-generator --dry-run --net tn10");
+        println!("   kaspa-tx-generator --dry-run --utxo-snapshot utxos.csv");
+        println!("   kaspa-tx-generator --find-max-tps --net tn10 --start-tps 50 --trial-secs 60");
+        println!("   kaspa-tx-generator --scenario scenario.example.toml --net tn10");
+        println!("   kaspa-tx-generator --rbf --net tn10 --rbf-replacements 4 --rbf-delays-ms 500,1000,2000");
+        println!("   kaspa-tx-generator --balances wallets.csv --net tn10 --json");
+        println!("   kaspa-tx-generator --fund-wallets wallets.csv --amount 10 --net tn10");
+        println!("   kaspa-tx-generator --consolidate --net tn10");
+        println!("   kaspa-tx-generator --sweep-to kaspatest:qq... --net tn10");
+        println!("   kaspa-tx-generator --sweep-wallets wallets.csv --to kaspatest:qq... --net tn10");
+        println!("   kaspa-tx-generator --mock-node --net tn10 --mock-fund-utxos 50");
+        println!("   kaspa-tx-generator --with-mock-node --net tn10");
         println!();
         println!("📁 Output Formats:");
         println!("   TXT      - Human-readable text with separators");
@@ -335,8 +496,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    // If we get here, show default help
-    println!("🔐 Kaspa Wallet Generator");
-    println!("Use --help for usage information");
-    Ok(())
+    // `--spam`, or no command at all as in the Docker image
+    let config = load_config(&args).await?;
+    spam::run(&config).await
 }
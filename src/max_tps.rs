@@ -0,0 +1,254 @@
+use std::{error::Error, time::Duration};
+
+use crate::{
+    cli,
+    config::Config,
+    profile::{Curve, Profile},
+    scheduler::{Scheduler, SystemClock},
+    spam::{self, percentile, SendEnd, Sender, Session},
+};
+
+/// Lowest achieved fraction of the trial target that still counts as keeping up.
+const MIN_ACHIEVED_RATIO: f64 = 0.9;
+/// How long a back-off waits at most for our pending transactions to confirm.
+const MAX_SETTLE: Duration = Duration::from_secs(120);
+
+/// Search and threshold settings of `--find-max-tps`.
+struct SearchOptions {
+    start_tps: f64,
+    /// Added after every passing trial; `None` doubles the rate instead
+    step_tps: Option<f64>,
+    max_tps: f64,
+    precision_tps: f64,
+    trial: Duration,
+    cooldown: Duration,
+    max_reject_rate: f64,
+    max_submit_latency: Duration,
+    max_confirmation_lag: Duration,
+}
+
+impl SearchOptions {
+    fn from_args(config: &Config, args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let max_tps: f64 = cli::parse_arg(args, "--max-tps")?.unwrap_or(10_000.0);
+        Ok(SearchOptions {
+            start_tps: cli::parse_arg::<f64>(args, "--start-tps")?.unwrap_or(10.0).max(1.0),
+            step_tps: cli::parse_arg::<f64>(args, "--step-tps")?.filter(|step| *step > 0.0),
+            max_tps: config.tps_cap().map_or(max_tps, |cap| max_tps.min(cap)),
+            precision_tps: cli::parse_arg::<f64>(args, "--precision-tps")?.unwrap_or(5.0).max(1.0),
+            trial: Duration::from_secs(cli::parse_arg::<u64>(args, "--trial-secs")?.unwrap_or(30).max(1)),
+            cooldown: Duration::from_secs(cli::parse_arg(args, "--cooldown-secs")?.unwrap_or(10)),
+            max_reject_rate: cli::parse_arg::<f64>(args, "--max-reject-pct")?.unwrap_or(1.0) / 100.0,
+            max_submit_latency: Duration::from_millis(cli::parse_arg(args, "--max-latency-ms")?.unwrap_or(1000)),
+            max_confirmation_lag: Duration::from_secs(cli::parse_arg(args, "--max-lag-secs")?.unwrap_or(30)),
+        })
+    }
+
+    fn next_rate(&self, tps: f64) -> f64 {
+        match self.step_tps {
+            Some(step) => tps + step,
+            None => tps * 2.0,
+        }
+        .min(self.max_tps)
+    }
+}
+
+/// Outcome of sending at one fixed rate.
+struct Trial {
+    target: f64,
+    achieved: f64,
+    submitted: u64,
+    failed: u64,
+    submit_p50: Duration,
+    submit_p95: Duration,
+    confirm_p95: Option<Duration>,
+    /// Why the trial crossed a threshold
+    failure: Option<String>,
+}
+
+impl Trial {
+    fn reject_rate(&self) -> f64 {
+        let total = self.submitted + self.failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.failed as f64 / total as f64
+        }
+    }
+
+    fn describe(&self) -> String {
+        let confirm = self.confirm_p95.map_or("-".to_string(), |lag| format!("{:.1}s", lag.as_secs_f64()));
+        format!(
+            "achieved {:.1}, rejected {:.1}%, submit p50/p95 {}/{}ms, confirm p95 {}",
+            self.achieved,
+            self.reject_rate() * 100.0,
+            self.submit_p50.as_millis(),
+            self.submit_p95.as_millis(),
+            confirm
+        )
+    }
+}
+
+/// `--find-max-tps`: raises the rate trial by trial until rejections, submit
+/// latency, confirmation lag or falling behind the target cross a threshold, backs
+/// off and binary-searches between the last passing and the first failing rate.
+pub async fn run(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = SearchOptions::from_args(config, args)?;
+    let session = spam::prepare(config, "📈 Maximum TPS search").await?;
+    spam::split(config, &session).await?;
+    let mut sender = Sender::start(config, &session);
+    println!(
+        "🔍 Trials of {}s from {} TPS up to {} TPS; thresholds: {:.1}% rejected, submit p95 {}ms, confirm p95 {}s",
+        options.trial.as_secs(),
+        options.start_tps,
+        options.max_tps,
+        options.max_reject_rate * 100.0,
+        options.max_submit_latency.as_millis(),
+        options.max_confirmation_lag.as_secs()
+    );
+
+    let mut trials = 0;
+    let mut best: Option<Trial> = None;
+    let mut failing: Option<Trial> = None;
+    let mut target = options.start_tps.min(options.max_tps);
+    let mut exhausted;
+    // Ramp up until a trial fails or the ceiling passes
+    loop {
+        trials += 1;
+        let (trial, end) = run_trial(config, &options, &session, &mut sender, trials, target).await;
+        exhausted = end == SendEnd::BudgetExhausted;
+        if trial.failure.is_some() {
+            failing = Some(trial);
+            break;
+        }
+        best = Some(trial);
+        if exhausted || target >= options.max_tps {
+            break;
+        }
+        target = options.next_rate(target);
+    }
+    // Binary search between the last passing and the first failing rate, backing
+    // off after every failure
+    let mut backing_off = true;
+    while !exhausted {
+        let Some(high) = failing.as_ref().map(|trial| trial.target) else {
+            break;
+        };
+        let low = best.as_ref().map_or(0.0, |trial| trial.target);
+        if high - low <= options.precision_tps {
+            break;
+        }
+        if backing_off {
+            settle(&options, &session).await;
+        }
+        trials += 1;
+        let (trial, end) = run_trial(config, &options, &session, &mut sender, trials, (low + high) / 2.0).await;
+        exhausted = end == SendEnd::BudgetExhausted;
+        backing_off = trial.failure.is_some();
+        if backing_off {
+            failing = Some(trial);
+        } else {
+            best = Some(trial);
+        }
+    }
+    sender.finish().await;
+
+    println!();
+    match &best {
+        Some(trial) => {
+            println!("🏁 Maximum sustained TPS: {:.1}", trial.target);
+            println!("   At that rate:   {}", trial.describe());
+        }
+        None => println!("🏁 No rate passed the thresholds"),
+    }
+    match &failing {
+        Some(trial) => {
+            println!("   Lowest failing: {:.1} TPS, {}", trial.target, trial.failure.as_deref().unwrap_or_default())
+        }
+        None if !exhausted => {
+            println!("   Reached the {} TPS ceiling without failing; raise --max-tps", options.max_tps)
+        }
+        None => {}
+    }
+    if exhausted {
+        println!("   ⚠️  The fee budget ended the search early");
+    }
+    println!("   Trials:         {}", trials);
+    println!("   Fees paid:      {}", session.budget.describe());
+    Ok(())
+}
+
+/// Sends at `target` TPS for one trial and judges it against the thresholds.
+async fn run_trial(
+    config: &Config,
+    options: &SearchOptions,
+    session: &Session,
+    sender: &mut Sender,
+    number: usize,
+    target: f64,
+) -> (Trial, SendEnd) {
+    let stats = sender.stats().clone();
+    let (submitted_before, failed_before) = (stats.submitted(), stats.failed());
+    stats.take_latencies();
+    session.take_confirmation_lags();
+
+    let profile = Profile::new(Curve::Constant(target), None);
+    let mut scheduler = Scheduler::new(SystemClock::new(), profile, Duration::from_millis(config.millis_per_tick));
+    let end = sender.send(config, &mut scheduler, Some(options.trial)).await;
+    // Judge the trial on the outcome of everything it sent
+    let stragglers = stats.drain().await;
+    if stragglers > 0 {
+        println!("   ⚠️  {} submits still in flight count towards the next trial", stragglers);
+    }
+
+    let mut latencies = stats.take_latencies();
+    let mut lags = session.take_confirmation_lags();
+    let mut trial = Trial {
+        target,
+        achieved: scheduler.achieved_tps(),
+        submitted: stats.submitted() - submitted_before,
+        failed: stats.failed() - failed_before,
+        submit_p50: percentile(&mut latencies, 50.0),
+        submit_p95: percentile(&mut latencies, 95.0),
+        confirm_p95: (!lags.is_empty()).then(|| percentile(&mut lags, 95.0)),
+        failure: None,
+    };
+    trial.failure = judge(options, &trial);
+    let verdict = match &trial.failure {
+        Some(reason) => format!("❌ {}", reason),
+        None => "✅".to_string(),
+    };
+    println!("   Trial {}: {:.1} TPS, {} {}", number, target, trial.describe(), verdict);
+    (trial, end)
+}
+
+fn judge(options: &SearchOptions, trial: &Trial) -> Option<String> {
+    if trial.reject_rate() > options.max_reject_rate {
+        return Some(format!("rejection rate above {:.1}%", options.max_reject_rate * 100.0));
+    }
+    if trial.submit_p95 > options.max_submit_latency {
+        return Some(format!("submit p95 above {}ms", options.max_submit_latency.as_millis()));
+    }
+    match trial.confirm_p95 {
+        Some(lag) if lag > options.max_confirmation_lag => {
+            return Some(format!("confirmation p95 above {}s", options.max_confirmation_lag.as_secs()))
+        }
+        None if trial.submitted > 0 && options.trial > options.max_confirmation_lag => {
+            return Some(format!("nothing confirmed within {}s", options.trial.as_secs()))
+        }
+        _ => {}
+    }
+    if trial.achieved < trial.target * MIN_ACHIEVED_RATIO {
+        return Some("could not keep up with the target (UTXO pool or submit throughput)".to_string());
+    }
+    None
+}
+
+/// Backs off after a failed trial: waits the cooldown, then until our pending
+/// transactions have confirmed so the next trial starts from a drained mempool.
+async fn settle(options: &SearchOptions, session: &Session) {
+    tokio::time::sleep(options.cooldown).await;
+    let started = tokio::time::Instant::now();
+    while session.pool_stats().pending_change > 0 && started.elapsed() < MAX_SETTLE {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,699 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use kaspa_addresses::Address;
+use kaspa_consensus_core::{
+    constants::SOMPI_PER_KASPA,
+    tx::{ScriptPublicKey, Transaction, TransactionId, TransactionOutpoint, UtxoEntry},
+    Hash,
+};
+use kaspa_notify::subscription::Command;
+use kaspa_rpc_core::{
+    api::ops::RpcApiOps, GetBlockDagInfoResponse, GetCurrentNetworkResponse, GetFeeEstimateResponse, GetInfoResponse,
+    GetMempoolEntriesByAddressesRequest, GetMempoolEntriesByAddressesResponse, GetMempoolEntryRequest,
+    GetMempoolEntryResponse, GetServerInfoResponse, GetUtxosByAddressesRequest, GetUtxosByAddressesResponse,
+    Notification, NotifyUtxosChangedRequest, NotifyUtxosChangedResponse, NotifyVirtualChainChangedRequest,
+    NotifyVirtualChainChangedResponse, NotifyVirtualDaaScoreChangedRequest, NotifyVirtualDaaScoreChangedResponse,
+    PingResponse, RpcAcceptedTransactionIds, RpcFeeEstimate, RpcFeerateBucket, RpcHash, RpcMempoolEntry,
+    RpcMempoolEntryByAddress, RpcUtxosByAddressesEntry, SubmitTransactionReplacementRequest,
+    SubmitTransactionReplacementResponse, SubmitTransactionRequest, SubmitTransactionResponse,
+    UtxosChangedNotification, VirtualChainChangedNotification, VirtualDaaScoreChangedNotification,
+};
+use kaspa_txscript::pay_to_address_script;
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, UnboundedSender},
+    time::Instant,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    cli,
+    config::{Config, Network},
+    keys,
+    rejection::Rejection,
+    tx::{self, format_kas},
+};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:18110";
+const DEFAULT_FUND_SOMPI: u64 = 1_000 * SOMPI_PER_KASPA;
+const DEFAULT_BLOCKS_PER_SECOND: u64 = 10;
+/// Lowest fee rate (sompi per gram) the mock mempool accepts, as on a real node.
+const MIN_RELAY_FEE_RATE: u64 = 1;
+
+// ----------------------- options -----------------------
+/// Mock node settings, read from `--mock-*` arguments.
+#[derive(Debug, Clone)]
+pub struct MockOptions {
+    pub listen: String,
+    pub network: Network,
+    /// Address holding the initial UTXO set
+    pub fund: Address,
+    pub fund_sompi: u64,
+    pub fund_utxos: usize,
+    /// Time a transaction spends in the mempool before it is accepted into a block
+    pub latency: Duration,
+    /// Rejects every Nth submitted transaction (0 = never)
+    pub reject_every: u64,
+    /// Reason given for the injected rejections
+    pub reject_kind: Rejection,
+    /// Closes the connection instead of answering every Nth accepted submit (0 = never)
+    pub drop_every: u64,
+    /// DAA score increments per second
+    pub blocks_per_second: u64,
+}
+
+impl MockOptions {
+    /// Funds `--mock-fund ADDRESS`, or the address of `PRIVATE_KEY_HEX` by default.
+    pub fn from_args(config: &Config, args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let fund = match cli::arg_value(args, "--mock-fund") {
+            Some(address) => keys::parse_address(&address, config.network)?,
+            None => keys::address_for(&keys::keypair_from_hex(config.private_key_hex()?)?, config.network),
+        };
+        Ok(MockOptions {
+            listen: cli::arg_value(args, "--mock-listen").unwrap_or_else(|| DEFAULT_LISTEN.to_string()),
+            network: config.network,
+            fund,
+            fund_sompi: cli::parse_arg(args, "--mock-fund-sompi")?.unwrap_or(DEFAULT_FUND_SOMPI),
+            fund_utxos: cli::parse_arg::<usize>(args, "--mock-fund-utxos")?.unwrap_or(1).max(1),
+            latency: Duration::from_millis(cli::parse_arg(args, "--mock-latency-ms")?.unwrap_or(500)),
+            reject_every: cli::parse_arg(args, "--mock-reject-every")?.unwrap_or(0),
+            reject_kind: cli::parse_arg(args, "--mock-reject-kind")?.unwrap_or(Rejection::Other),
+            drop_every: cli::parse_arg(args, "--mock-drop-every")?.unwrap_or(0),
+            blocks_per_second: cli::parse_arg::<u64>(args, "--mock-bps")?.unwrap_or(DEFAULT_BLOCKS_PER_SECOND).max(1),
+        })
+    }
+}
+
+// ----------------------- node state -----------------------
+struct MempoolEntry {
+    id: TransactionId,
+    /// Kept to answer replacements with the transaction they replaced
+    tx: Transaction,
+    fee: u64,
+    mass: u64,
+    inputs: Vec<TransactionOutpoint>,
+    outputs: Vec<(TransactionOutpoint, UtxoEntry)>,
+    accept_at: Instant,
+}
+
+struct Subscriber {
+    connection: u64,
+    sender: UnboundedSender<String>,
+    daa_score: bool,
+    /// Follows VirtualChainChanged, with accepted transaction ids if set
+    virtual_chain: Option<bool>,
+    /// Addresses and their scripts followed for UtxosChanged
+    addresses: Vec<(Address, ScriptPublicKey)>,
+}
+
+struct NodeState {
+    virtual_daa_score: u64,
+    utxos: HashMap<TransactionOutpoint, UtxoEntry>,
+    /// Accepted in submission order; the latency is fixed, so this is also acceptance order
+    mempool: VecDeque<MempoolEntry>,
+    /// Outpoints spent by mempool transactions
+    spent: HashSet<TransactionOutpoint>,
+    /// Outputs of mempool transactions, which chained transactions may spend
+    unconfirmed: HashMap<TransactionOutpoint, UtxoEntry>,
+    submitted: u64,
+    /// Submits accepted into the mempool, counted for `drop_every`
+    accepted_submits: u64,
+    subscribers: Vec<Subscriber>,
+}
+
+/// A simulated node: a UTXO set, a mempool that accepts transactions after a fixed
+/// latency, and a DAA score that advances at a fixed block rate.
+struct MockNode {
+    options: MockOptions,
+    state: Mutex<NodeState>,
+}
+
+impl MockNode {
+    fn new(options: MockOptions) -> Self {
+        let script_public_key = pay_to_address_script(&options.fund);
+        let amount = options.fund_sompi / options.fund_utxos as u64;
+        let utxos = (0..options.fund_utxos)
+            .map(|index| {
+                let mut id = [0u8; 32];
+                id[..8].copy_from_slice(&(index as u64 + 1).to_le_bytes());
+                let outpoint = TransactionOutpoint::new(Hash::from_bytes(id), 0);
+                (outpoint, UtxoEntry::new(amount, script_public_key.clone(), 0, false))
+            })
+            .collect();
+        let state = NodeState {
+            virtual_daa_score: 1,
+            utxos,
+            mempool: VecDeque::new(),
+            spent: HashSet::new(),
+            unconfirmed: HashMap::new(),
+            submitted: 0,
+            accepted_submits: 0,
+            subscribers: Vec::new(),
+        };
+        MockNode { options, state: Mutex::new(state) }
+    }
+
+    fn handle(
+        &self,
+        connection: u64,
+        sender: &UnboundedSender<String>,
+        op: RpcApiOps,
+        params: Value,
+    ) -> Result<Value, String> {
+        let response = match op {
+            RpcApiOps::Ping => to_value(PingResponse {}),
+            RpcApiOps::GetInfo => to_value(GetInfoResponse {
+                p2p_id: "mock-node".to_string(),
+                mempool_size: self.state.lock().unwrap().mempool.len() as u64,
+                server_version: env!("CARGO_PKG_VERSION").to_string(),
+                is_utxo_indexed: true,
+                is_synced: true,
+                has_notify_command: true,
+                has_message_id: true,
+            }),
+            RpcApiOps::GetServerInfo => to_value(GetServerInfoResponse {
+                rpc_api_version: 1,
+                rpc_api_revision: 0,
+                server_version: env!("CARGO_PKG_VERSION").to_string(),
+                network_id: self.options.network.network_id(),
+                has_utxo_index: true,
+                is_synced: true,
+                virtual_daa_score: self.state.lock().unwrap().virtual_daa_score,
+            }),
+            RpcApiOps::GetCurrentNetwork => {
+                to_value(GetCurrentNetworkResponse { network: self.options.network.network_id().network_type() })
+            }
+            RpcApiOps::GetBlockDagInfo => {
+                let virtual_daa_score = self.state.lock().unwrap().virtual_daa_score;
+                to_value(GetBlockDagInfoResponse {
+                    network: self.options.network.network_id(),
+                    block_count: virtual_daa_score,
+                    header_count: virtual_daa_score,
+                    tip_hashes: vec![RpcHash::default()],
+                    difficulty: 1.0,
+                    past_median_time: 0,
+                    virtual_parent_hashes: vec![RpcHash::default()],
+                    pruning_point_hash: RpcHash::default(),
+                    virtual_daa_score,
+                    sink: RpcHash::default(),
+                })
+            }
+            RpcApiOps::GetFeeEstimate => {
+                let bucket = |feerate, estimated_seconds| RpcFeerateBucket { feerate, estimated_seconds };
+                let latency = self.options.latency.as_secs_f64();
+                to_value(GetFeeEstimateResponse {
+                    estimate: RpcFeeEstimate {
+                        priority_bucket: bucket(MIN_RELAY_FEE_RATE as f64, latency),
+                        normal_buckets: vec![bucket(MIN_RELAY_FEE_RATE as f64, latency)],
+                        low_buckets: vec![bucket(MIN_RELAY_FEE_RATE as f64, latency)],
+                    },
+                })
+            }
+            RpcApiOps::GetUtxosByAddresses => {
+                let request: GetUtxosByAddressesRequest = from_value(params)?;
+                let state = self.state.lock().unwrap();
+                let entries = request
+                    .addresses
+                    .into_iter()
+                    .flat_map(|address| {
+                        let script_public_key = pay_to_address_script(&address);
+                        state
+                            .utxos
+                            .iter()
+                            .filter(move |(_, entry)| entry.script_public_key == script_public_key)
+                            .map(move |(outpoint, entry)| utxo_entry(&address, outpoint, entry))
+                    })
+                    .collect();
+                to_value(GetUtxosByAddressesResponse { entries })
+            }
+            RpcApiOps::GetMempoolEntry => {
+                let request: GetMempoolEntryRequest = from_value(params)?;
+                let state = self.state.lock().unwrap();
+                let Some(entry) = state.mempool.iter().find(|entry| entry.id == request.transaction_id) else {
+                    return Err(format!("Transaction {} not found", request.transaction_id));
+                };
+                to_value(GetMempoolEntryResponse { mempool_entry: mempool_entry(entry) })
+            }
+            RpcApiOps::GetMempoolEntriesByAddresses => {
+                let request: GetMempoolEntriesByAddressesRequest = from_value(params)?;
+                let state = self.state.lock().unwrap();
+                let entries = request
+                    .addresses
+                    .into_iter()
+                    .map(|address| {
+                        let script_public_key = pay_to_address_script(&address);
+                        let owned = |outpoint: &TransactionOutpoint| {
+                            state
+                                .utxos
+                                .get(outpoint)
+                                .or_else(|| state.unconfirmed.get(outpoint))
+                                .is_some_and(|entry| entry.script_public_key == script_public_key)
+                        };
+                        let pays = |(_, output): &(TransactionOutpoint, UtxoEntry)| {
+                            output.script_public_key == script_public_key
+                        };
+                        let sending = state.mempool.iter().filter(|entry| entry.inputs.iter().any(owned));
+                        let receiving = state.mempool.iter().filter(|entry| entry.outputs.iter().any(pays));
+                        RpcMempoolEntryByAddress {
+                            sending: sending.map(mempool_entry).collect(),
+                            receiving: receiving.map(mempool_entry).collect(),
+                            address,
+                        }
+                    })
+                    .collect();
+                to_value(GetMempoolEntriesByAddressesResponse { entries })
+            }
+            RpcApiOps::SubmitTransaction => {
+                let request: SubmitTransactionRequest = from_value(params)?;
+                let tx = Transaction::try_from(request.transaction).map_err(|e| e.to_string())?;
+                let (transaction_id, _) = self.submit(tx, false)?;
+                to_value(SubmitTransactionResponse { transaction_id })
+            }
+            RpcApiOps::SubmitTransactionReplacement => {
+                let request: SubmitTransactionReplacementRequest = from_value(params)?;
+                let tx = Transaction::try_from(request.transaction).map_err(|e| e.to_string())?;
+                let (transaction_id, replaced) = self.submit(tx, true)?;
+                let replaced_transaction = replaced.as_ref().expect("replacements replace a transaction").into();
+                to_value(SubmitTransactionReplacementResponse { transaction_id, replaced_transaction })
+            }
+            RpcApiOps::NotifyUtxosChanged => {
+                let request: NotifyUtxosChangedRequest = from_value(params)?;
+                let addresses = request.addresses.into_iter().map(|address| {
+                    let script_public_key = pay_to_address_script(&address);
+                    (address, script_public_key)
+                });
+                let mut state = self.state.lock().unwrap();
+                let subscriber = subscriber(&mut state, connection, sender);
+                match request.command {
+                    Command::Start => subscriber.addresses.extend(addresses),
+                    Command::Stop => {
+                        let stopped: Vec<Address> = addresses.map(|(address, _)| address).collect();
+                        subscriber.addresses.retain(|(address, _)| !stopped.contains(address));
+                    }
+                }
+                to_value(NotifyUtxosChangedResponse {})
+            }
+            RpcApiOps::NotifyVirtualDaaScoreChanged => {
+                let request: NotifyVirtualDaaScoreChangedRequest = from_value(params)?;
+                let mut state = self.state.lock().unwrap();
+                subscriber(&mut state, connection, sender).daa_score = request.command == Command::Start;
+                to_value(NotifyVirtualDaaScoreChangedResponse {})
+            }
+            RpcApiOps::NotifyVirtualChainChanged => {
+                let request: NotifyVirtualChainChangedRequest = from_value(params)?;
+                let mut state = self.state.lock().unwrap();
+                subscriber(&mut state, connection, sender).virtual_chain =
+                    (request.command == Command::Start).then_some(request.include_accepted_transaction_ids);
+                to_value(NotifyVirtualChainChangedResponse {})
+            }
+            _ => return Err(format!("{:?} is not supported by the mock node", op)),
+        };
+        Ok(response)
+    }
+
+    /// Validates `tx` against the UTXO set and mempool and queues it for acceptance.
+    /// Signatures are not checked. With `replace`, `tx` must double spend exactly one
+    /// mempool transaction at a higher fee rate, which it replaces and returns.
+    fn submit(&self, tx: Transaction, replace: bool) -> Result<(TransactionId, Option<Transaction>), String> {
+        let id = tx.id();
+        let mut state = self.state.lock().unwrap();
+        state.submitted += 1;
+        if state.submitted.checked_rem(self.options.reject_every) == Some(0) {
+            return Err(injected_rejection(self.options.reject_kind, id));
+        }
+        if state.mempool.iter().any(|entry| entry.id == id) {
+            return Err(format!("Rejected transaction {}: transaction {} is already in the mempool", id, id));
+        }
+
+        let conflicts: Vec<usize> = if replace {
+            let double_spends =
+                |entry: &MempoolEntry| tx.inputs.iter().any(|input| entry.inputs.contains(&input.previous_outpoint));
+            state.mempool.iter().enumerate().filter(|(_, entry)| double_spends(entry)).map(|(index, _)| index).collect()
+        } else {
+            Vec::new()
+        };
+        if replace && conflicts.len() != 1 {
+            return Err(format!(
+                "Rejected transaction {}: replacement by fee needs exactly one double spend in the mempool, found {}",
+                id,
+                conflicts.len()
+            ));
+        }
+        let mut input_amounts = Vec::with_capacity(tx.inputs.len());
+        for input in &tx.inputs {
+            let outpoint = input.previous_outpoint;
+            let replaced = conflicts.first().is_some_and(|&index| state.mempool[index].inputs.contains(&outpoint));
+            if state.spent.contains(&outpoint) && !replaced {
+                return Err(format!(
+                    "Rejected transaction {}: output {} already spent by a transaction in the memory pool",
+                    id, outpoint
+                ));
+            }
+            match state.utxos.get(&outpoint).or_else(|| state.unconfirmed.get(&outpoint)) {
+                Some(entry) => input_amounts.push(entry.amount),
+                None => {
+                    return Err(format!(
+                        "Rejected transaction {}: transaction is an orphan where orphan is disallowed",
+                        id
+                    ))
+                }
+            }
+        }
+        let output_amounts: Vec<u64> = tx.outputs.iter().map(|output| output.value).collect();
+        let total_in: u64 = input_amounts.iter().sum();
+        let total_out: u64 = output_amounts.iter().sum();
+        if total_out > total_in {
+            return Err(format!("Rejected transaction {}: outputs {} exceed inputs {}", id, total_out, total_in));
+        }
+        let mass = tx::estimated_mass(&input_amounts, &output_amounts, tx.payload.len());
+        if mass > tx::MAXIMUM_STANDARD_TRANSACTION_MASS {
+            return Err(format!("Rejected transaction {}: mass {} is larger than the standard limit", id, mass));
+        }
+        let min_fee = tx::required_fee(MIN_RELAY_FEE_RATE, mass);
+        if total_in - total_out < min_fee {
+            return Err(format!(
+                "Rejected transaction {}: fee {} is under the required amount of {}",
+                id,
+                total_in - total_out,
+                min_fee
+            ));
+        }
+
+        let fee = total_in - total_out;
+        let replaced = match conflicts.first() {
+            Some(&index) => {
+                let old = &state.mempool[index];
+                if fee as u128 * old.mass as u128 <= old.fee as u128 * mass as u128 {
+                    return Err(format!(
+                        "Rejected transaction {}: fee rate {:.2} is not greater than the fee rate {:.2} of replaced transaction {}",
+                        id,
+                        fee as f64 / mass as f64,
+                        old.fee as f64 / old.mass as f64,
+                        old.id
+                    ));
+                }
+                let old = state.mempool.remove(index).unwrap();
+                for outpoint in &old.inputs {
+                    state.spent.remove(outpoint);
+                }
+                for (outpoint, _) in &old.outputs {
+                    state.unconfirmed.remove(outpoint);
+                }
+                debug!("Mock node replaced {} with {}", old.id, id);
+                Some(old.tx)
+            }
+            None => None,
+        };
+
+        let inputs: Vec<TransactionOutpoint> = tx.inputs.iter().map(|input| input.previous_outpoint).collect();
+        let outputs: Vec<(TransactionOutpoint, UtxoEntry)> = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let entry = UtxoEntry::new(output.value, output.script_public_key.clone(), 0, false);
+                (TransactionOutpoint::new(id, index as u32), entry)
+            })
+            .collect();
+        state.spent.extend(inputs.iter().copied());
+        state.unconfirmed.extend(outputs.iter().cloned());
+        let accept_at = Instant::now() + self.options.latency;
+        state.mempool.push_back(MempoolEntry { id, tx, fee, mass, inputs, outputs, accept_at });
+        debug!("Mock node accepted {} into the mempool ({} pending)", id, state.mempool.len());
+        Ok((id, replaced))
+    }
+
+    /// Counts an accepted submit and returns true if its connection should be dropped
+    /// before the answer, as if the node had gone away after taking the transaction.
+    fn drop_due(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.accepted_submits += 1;
+        state.accepted_submits.checked_rem(self.options.drop_every) == Some(0)
+    }
+
+    /// Advances the DAA score by one block, accepts due mempool transactions into it
+    /// and notifies subscribers.
+    fn next_block(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.virtual_daa_score += 1;
+        let virtual_daa_score = state.virtual_daa_score;
+        let now = Instant::now();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut accepted = Vec::new();
+        while state.mempool.front().is_some_and(|entry| entry.accept_at <= now) {
+            let entry = state.mempool.pop_front().unwrap();
+            for outpoint in entry.inputs {
+                state.spent.remove(&outpoint);
+                if let Some(spent) = state.utxos.remove(&outpoint) {
+                    removed.push((outpoint, spent));
+                }
+            }
+            for (outpoint, mut output) in entry.outputs {
+                state.unconfirmed.remove(&outpoint);
+                output.block_daa_score = virtual_daa_score;
+                state.utxos.insert(outpoint, output.clone());
+                added.push((outpoint, output));
+            }
+            debug!("Mock node accepted {} at DAA score {}", entry.id, virtual_daa_score);
+            accepted.push(entry.id);
+        }
+        // Every block joins the virtual chain and accepts the transactions it holds
+        let mut block = [0u8; 32];
+        block[24..].copy_from_slice(&virtual_daa_score.to_be_bytes());
+        let block = RpcHash::from_bytes(block);
+
+        state.subscribers.retain(|subscriber| {
+            let mut sent = true;
+            if !subscriber.addresses.is_empty() && (!added.is_empty() || !removed.is_empty()) {
+                let matching = |changes: &[(TransactionOutpoint, UtxoEntry)]| {
+                    changes
+                        .iter()
+                        .filter_map(|(outpoint, entry)| {
+                            let (address, _) =
+                                subscriber.addresses.iter().find(|(_, script)| *script == entry.script_public_key)?;
+                            Some(utxo_entry(address, outpoint, entry))
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let (added, removed) = (matching(&added), matching(&removed));
+                if !added.is_empty() || !removed.is_empty() {
+                    let changed = UtxosChangedNotification { added: Arc::new(added), removed: Arc::new(removed) };
+                    sent &=
+                        notify(subscriber, RpcApiOps::UtxosChangedNotification, Notification::UtxosChanged(changed));
+                }
+            }
+            if let Some(include_accepted_transaction_ids) = subscriber.virtual_chain {
+                let accepted_transaction_ids = if include_accepted_transaction_ids {
+                    vec![RpcAcceptedTransactionIds {
+                        accepting_block_hash: block,
+                        accepted_transaction_ids: accepted.clone(),
+                    }]
+                } else {
+                    Vec::new()
+                };
+                let changed = VirtualChainChangedNotification {
+                    removed_chain_block_hashes: Arc::new(Vec::new()),
+                    added_chain_block_hashes: Arc::new(vec![block]),
+                    accepted_transaction_ids: Arc::new(accepted_transaction_ids),
+                };
+                sent &= notify(
+                    subscriber,
+                    RpcApiOps::VirtualChainChangedNotification,
+                    Notification::VirtualChainChanged(changed),
+                );
+            }
+            if subscriber.daa_score {
+                let changed = VirtualDaaScoreChangedNotification { virtual_daa_score };
+                sent &= notify(
+                    subscriber,
+                    RpcApiOps::VirtualDaaScoreChangedNotification,
+                    Notification::VirtualDaaScoreChanged(changed),
+                );
+            }
+            sent
+        });
+    }
+}
+
+fn subscriber<'a>(state: &'a mut NodeState, connection: u64, sender: &UnboundedSender<String>) -> &'a mut Subscriber {
+    let index = match state.subscribers.iter().position(|subscriber| subscriber.connection == connection) {
+        Some(index) => index,
+        None => {
+            state.subscribers.push(Subscriber {
+                connection,
+                sender: sender.clone(),
+                daa_score: false,
+                virtual_chain: None,
+                addresses: Vec::new(),
+            });
+            state.subscribers.len() - 1
+        }
+    };
+    &mut state.subscribers[index]
+}
+
+/// A rejection message in the node's wording for `kind`.
+fn injected_rejection(kind: Rejection, id: TransactionId) -> String {
+    let reason = match kind {
+        Rejection::Orphan => "transaction is an orphan where orphan is disallowed".to_string(),
+        Rejection::DoubleSpend => format!("output {}:0 already spent by a transaction in the memory pool", id),
+        Rejection::FeeTooLow => "fee 0 is under the required amount of 1".to_string(),
+        Rejection::MassTooLarge => "mass 100001 is larger than the standard limit".to_string(),
+        Rejection::MempoolFull => "transaction could not be added because the mempool is full".to_string(),
+        Rejection::AlreadyInMempool => format!("transaction {} is already in the mempool", id),
+        Rejection::Other => "injected rejection".to_string(),
+    };
+    format!("Rejected transaction {}: {}", id, reason)
+}
+
+/// Queues a notification for `subscriber`. Returns false once its connection is gone.
+fn notify(subscriber: &Subscriber, op: RpcApiOps, notification: Notification) -> bool {
+    let message = json!({ "method": op, "params": notification });
+    subscriber.sender.send(message.to_string()).is_ok()
+}
+
+fn mempool_entry(entry: &MempoolEntry) -> RpcMempoolEntry {
+    RpcMempoolEntry { fee: entry.fee, transaction: (&entry.tx).into(), is_orphan: false }
+}
+
+fn utxo_entry(address: &Address, outpoint: &TransactionOutpoint, entry: &UtxoEntry) -> RpcUtxosByAddressesEntry {
+    RpcUtxosByAddressesEntry {
+        address: Some(address.clone()),
+        outpoint: (*outpoint).into(),
+        utxo_entry: entry.clone().into(),
+    }
+}
+
+fn to_value<T: serde::Serialize>(response: T) -> Value {
+    serde_json::to_value(response).expect("RPC responses serialize to JSON")
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, String> {
+    serde_json::from_value(params).map_err(|e| format!("Invalid request: {}", e))
+}
+
+// ----------------------- server -----------------------
+/// Starts a mock node serving wRPC JSON on `options.listen` in the background and
+/// returns the bound address. Listening on port 0 picks a free port.
+pub async fn spawn(options: MockOptions) -> Result<SocketAddr, Box<dyn Error>> {
+    let listener = TcpListener::bind(&options.listen).await?;
+    let local_addr = listener.local_addr()?;
+    info!(
+        "Mock node on ws://{} ({}): {} in {} UTXOs for {}, {}ms acceptance, {} BPS",
+        local_addr,
+        options.network,
+        format_kas(options.fund_sompi),
+        options.fund_utxos,
+        String::from(&options.fund),
+        options.latency.as_millis(),
+        options.blocks_per_second
+    );
+    let node = Arc::new(MockNode::new(options));
+
+    let clock = node.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(1000 / clock.options.blocks_per_second));
+        loop {
+            ticker.tick().await;
+            clock.next_block();
+        }
+    });
+    tokio::spawn(async move {
+        let mut next_connection = 0;
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    next_connection += 1;
+                    tokio::spawn(serve_connection(node.clone(), next_connection, stream, peer));
+                }
+                Err(e) => warn!("Mock node failed to accept a connection: {}", e),
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+/// `--mock-node` runs a standalone mock node until interrupted.
+pub async fn run(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = MockOptions::from_args(config, args)?;
+    let (reject_every, reject_kind, drop_every) = (options.reject_every, options.reject_kind, options.drop_every);
+    let local_addr = spawn(options).await?;
+    println!("🧪 Mock node listening on ws://{} ({})", local_addr, config.network);
+    println!("   Connect with KASPA_RPC_URL=ws://{} WRPC_ENCODING=json", local_addr);
+    if reject_every > 0 {
+        println!("   Rejecting every {}th transaction ({})", reject_every, reject_kind);
+    }
+    if drop_every > 0 {
+        println!("   Dropping the connection of every {}th accepted submit", drop_every);
+    }
+    tokio::signal::ctrl_c().await?;
+    println!("👋 Mock node stopped");
+    Ok(())
+}
+
+async fn serve_connection(node: Arc<MockNode>, connection: u64, stream: TcpStream, peer: SocketAddr) {
+    let websocket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            warn!("Mock node handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+    debug!("Mock node connection {} from {}", connection, peer);
+    let (mut sink, mut stream) = websocket.split();
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if sink.send(Message::Text(message)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let request: Value = match serde_json::from_str(&text) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Mock node received invalid JSON from {}: {}", peer, e);
+                continue;
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").cloned().unwrap_or(Value::Null);
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let op = serde_json::from_value::<RpcApiOps>(method.clone()).map_err(|_| format!("Unknown method {}", method));
+        let result = op.clone().and_then(|op| node.handle(connection, &sender, op, params));
+        if matches!(op, Ok(RpcApiOps::SubmitTransaction)) && result.is_ok() && node.drop_due() {
+            debug!("Mock node dropping connection {} before answering a submit", connection);
+            break;
+        }
+        let response = match result {
+            Ok(params) => json!({ "id": id, "method": method, "params": params }),
+            Err(message) => {
+                json!({ "id": id, "method": method, "error": { "code": 0, "message": message, "data": null } })
+            }
+        };
+        if sender.send(response.to_string()).is_err() {
+            break;
+        }
+    }
+
+    node.state.lock().unwrap().subscribers.retain(|subscriber| subscriber.connection != connection);
+    writer.abort();
+    debug!("Mock node connection {} from {} closed", connection, peer);
+}
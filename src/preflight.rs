@@ -0,0 +1,37 @@
+use std::error::Error;
+
+use crate::{backend::RpcBackend, config::Network};
+
+/// Checks run before anything is submitted: the node is on `network`, synced and
+/// serving a UTXO index. Addresses from the command line or wallet files are checked
+/// against `network` when they are parsed, see `keys::parse_address`.
+pub async fn check(client: &dyn RpcBackend, network: Network) -> Result<(), Box<dyn Error>> {
+    let expected = network.network_id();
+    let network_type = client.api().get_current_network().await?;
+    if network_type != expected.network_type() {
+        return Err(format!(
+            "Connected node does not look like {}: it reports network {}. Check --net / KASPA_NETWORK and KASPA_RPC_URL",
+            network, network_type
+        )
+        .into());
+    }
+    // GetCurrentNetwork carries no suffix, so testnets are told apart by the server info
+    let server_info = client.api().get_server_info().await?;
+    if server_info.network_id.suffix() != expected.suffix() {
+        return Err(format!(
+            "Connected node does not look like {}: it reports network {}. Check --net / KASPA_NETWORK and KASPA_RPC_URL",
+            network, server_info.network_id
+        )
+        .into());
+    }
+
+    let info = client.api().get_info().await?;
+    if !info.is_synced || !server_info.is_synced {
+        return Err("Connected node is not synced yet; wait for it to catch up before submitting".into());
+    }
+    if !info.is_utxo_indexed || !server_info.has_utxo_index {
+        return Err("Connected node has no UTXO index; restart kaspad with --utxoindex".into());
+    }
+    println!("🔎 Preflight: {} node, synced, UTXO index enabled", network);
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use std::{fs, str::FromStr, time::Duration};
+
+/// Resolution of `mean_tps`.
+const MEAN_STEP: Duration = Duration::from_secs(1);
+
+/// Target TPS over time, parsed from `TPS_PROFILE`:
+///
+/// - `constant:TPS`
+/// - `ramp:FROM,TO,SECS`: linear from FROM to TO over SECS, then held at TO
+/// - `steps:TPS@SECS,...`: each rate held for SECS, the last one held to the end
+/// - `sine:MEAN,AMPLITUDE,PERIOD_SECS`: a sinusoid around MEAN, e.g. a daily pattern
+/// - `burst:BASE,PEAK,EVERY_SECS,LENGTH_SECS`: PEAK for LENGTH_SECS at the start of
+///   every EVERY_SECS, BASE otherwise
+/// - `trace:PATH`: replay of `seconds,tps` CSV rows, each rate held until the next
+///   row and the last one to the end
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve {
+    Constant(f64),
+    Ramp {
+        from: f64,
+        to: f64,
+        secs: f64,
+    },
+    Steps(Vec<(f64, f64)>),
+    Sine {
+        mean: f64,
+        amplitude: f64,
+        period: f64,
+    },
+    Burst {
+        base: f64,
+        peak: f64,
+        every: f64,
+        length: f64,
+    },
+    /// `(seconds, tps)` points sorted by time
+    Trace(Vec<(f64, f64)>),
+}
+
+impl FromStr for Curve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+        let numbers = || -> Result<Vec<f64>, String> {
+            params
+                .split(',')
+                .map(|value| value.trim().parse::<f64>().map_err(|e| format!("invalid number {}: {}", value, e)))
+                .collect()
+        };
+        let curve = match (kind.trim().to_lowercase().as_str(), numbers()) {
+            ("constant", Ok(values)) if values.len() == 1 => Curve::Constant(values[0]),
+            ("ramp", Ok(values)) if values.len() == 3 => {
+                Curve::Ramp { from: values[0], to: values[1], secs: values[2] }
+            }
+            ("sine", Ok(values)) if values.len() == 3 && values[2] > 0.0 => {
+                Curve::Sine { mean: values[0], amplitude: values[1], period: values[2] }
+            }
+            ("burst", Ok(values)) if values.len() == 4 && values[2] > 0.0 => {
+                Curve::Burst { base: values[0], peak: values[1], every: values[2], length: values[3] }
+            }
+            ("steps", _) => Curve::Steps(parse_steps(params)?),
+            ("trace", _) => {
+                let contents =
+                    fs::read_to_string(params).map_err(|e| format!("cannot read trace {}: {}", params, e))?;
+                Curve::Trace(parse_trace(&contents)?)
+            }
+            _ => {
+                return Err(format!(
+                    "invalid profile {}. Expected constant:TPS, ramp:FROM,TO,SECS, steps:TPS@SECS,..., \
+                     sine:MEAN,AMPLITUDE,PERIOD_SECS, burst:BASE,PEAK,EVERY_SECS,LENGTH_SECS or trace:PATH",
+                    s
+                ))
+            }
+        };
+        if curve.rates().any(|tps| !tps.is_finite() || tps < 0.0) {
+            return Err(format!("profile {} has a negative or non-finite rate", s));
+        }
+        Ok(curve)
+    }
+}
+
+fn parse_steps(params: &str) -> Result<Vec<(f64, f64)>, String> {
+    let steps = params
+        .split(',')
+        .map(|step| {
+            let (tps, secs) = step.split_once('@').ok_or_else(|| format!("step {} is not TPS@SECS", step))?;
+            let tps = tps.trim().parse::<f64>().map_err(|e| format!("invalid step rate {}: {}", tps, e))?;
+            let secs = secs.trim().parse::<f64>().map_err(|e| format!("invalid step hold {}: {}", secs, e))?;
+            Ok((tps, secs))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if steps.is_empty() {
+        return Err("steps need at least one TPS@SECS".to_string());
+    }
+    Ok(steps)
+}
+
+/// Reads `seconds,tps` rows; a header or comment rows that do not parse are skipped.
+fn parse_trace(contents: &str) -> Result<Vec<(f64, f64)>, String> {
+    let mut points: Vec<(f64, f64)> = contents
+        .lines()
+        .filter_map(|line| {
+            let (secs, tps) = line.split_once(',')?;
+            Some((secs.trim().parse::<f64>().ok()?, tps.trim().parse::<f64>().ok()?))
+        })
+        .collect();
+    if points.is_empty() {
+        return Err("trace has no seconds,tps rows".to_string());
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(points)
+}
+
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Curve::Constant(tps) => write!(f, "constant {} TPS", tps),
+            Curve::Ramp { from, to, secs } => write!(f, "ramp {} -> {} TPS over {}s", from, to, secs),
+            Curve::Steps(steps) => {
+                let steps: Vec<String> = steps.iter().map(|(tps, secs)| format!("{}@{}s", tps, secs)).collect();
+                write!(f, "steps {}", steps.join(", "))
+            }
+            Curve::Sine { mean, amplitude, period } => {
+                write!(f, "sine {} ± {} TPS, period {}s", mean, amplitude, period)
+            }
+            Curve::Burst { base, peak, every, length } => {
+                write!(f, "{} TPS with {}s bursts of {} TPS every {}s", base, length, peak, every)
+            }
+            Curve::Trace(points) => write!(f, "trace of {} points", points.len()),
+        }
+    }
+}
+
+impl Curve {
+    /// Target rate `secs` into the run.
+    pub fn tps_at(&self, secs: f64) -> f64 {
+        match self {
+            Curve::Constant(tps) => *tps,
+            Curve::Ramp { from, to, secs: length } => {
+                let progress = if *length > 0.0 { (secs / length).min(1.0) } else { 1.0 };
+                from + (to - from) * progress
+            }
+            Curve::Steps(steps) => {
+                let mut start = 0.0;
+                for (tps, hold) in steps {
+                    start += hold;
+                    if secs < start {
+                        return *tps;
+                    }
+                }
+                steps.last().map(|(tps, _)| *tps).unwrap_or(0.0)
+            }
+            Curve::Sine { mean, amplitude, period } => {
+                (mean + amplitude * (2.0 * std::f64::consts::PI * secs / period).sin()).max(0.0)
+            }
+            Curve::Burst { base, peak, every, length } => {
+                if secs % every < *length {
+                    *peak
+                } else {
+                    *base
+                }
+            }
+            Curve::Trace(points) => {
+                let index = points.partition_point(|(start, _)| *start <= secs);
+                points[index.saturating_sub(1)].1
+            }
+        }
+    }
+
+    /// Every rate the curve names, for validation and peaks.
+    fn rates(&self) -> Box<dyn Iterator<Item = f64> + '_> {
+        match self {
+            Curve::Constant(tps) => Box::new(std::iter::once(*tps)),
+            Curve::Ramp { from, to, .. } => Box::new([*from, *to].into_iter()),
+            Curve::Steps(steps) => Box::new(steps.iter().map(|(tps, _)| *tps)),
+            Curve::Trace(points) => Box::new(points.iter().map(|(_, tps)| *tps)),
+            Curve::Sine { mean, amplitude, .. } => {
+                Box::new([mean + amplitude.abs(), (mean - amplitude.abs()).max(0.0)].into_iter())
+            }
+            Curve::Burst { base, peak, .. } => Box::new([*base, *peak].into_iter()),
+        }
+    }
+}
+
+// ----------------------- profile -----------------------
+/// The configured curve with the `UNLEASHED=false` cap applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    curve: Curve,
+    cap: Option<f64>,
+}
+
+impl Profile {
+    pub fn new(curve: Curve, cap: Option<f64>) -> Self {
+        Profile { curve, cap }
+    }
+
+    pub fn curve(&self) -> &Curve {
+        &self.curve
+    }
+
+    /// Target rate `elapsed` into the run.
+    pub fn tps_at(&self, elapsed: Duration) -> f64 {
+        let tps = self.curve.tps_at(elapsed.as_secs_f64());
+        self.cap.map_or(tps, |cap| tps.min(cap))
+    }
+
+    /// Highest rate the profile asks for.
+    pub fn peak_tps(&self) -> f64 {
+        let peak = self.curve.rates().fold(0.0, f64::max);
+        self.cap.map_or(peak, |cap| peak.min(cap))
+    }
+
+    /// True when the cap lowers the peak.
+    pub fn is_capped(&self) -> bool {
+        self.cap.is_some_and(|cap| self.curve.rates().any(|tps| tps > cap))
+    }
+
+    /// Average target over the first `duration` of the run, for planning.
+    pub fn mean_tps(&self, duration: Duration) -> f64 {
+        let steps = (duration.as_secs_f64() / MEAN_STEP.as_secs_f64()).ceil().max(1.0) as u32;
+        (0..steps).map(|step| self.tps_at(MEAN_STEP * step)).sum::<f64>() / steps as f64
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.cap {
+            Some(cap) if self.is_capped() => write!(f, "{}, capped at {} TPS", self.curve, cap),
+            _ => write!(f, "{}", self.curve),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_curve_kind() {
+        assert_eq!("constant:12.5".parse(), Ok(Curve::Constant(12.5)));
+        assert_eq!("Ramp: 1, 10, 60".parse(), Ok(Curve::Ramp { from: 1.0, to: 10.0, secs: 60.0 }));
+        assert_eq!("steps:5@10,20@30".parse(), Ok(Curve::Steps(vec![(5.0, 10.0), (20.0, 30.0)])));
+        assert_eq!("sine:50,20,3600".parse(), Ok(Curve::Sine { mean: 50.0, amplitude: 20.0, period: 3600.0 }));
+        assert_eq!("burst:10,100,60,5".parse(), Ok(Curve::Burst { base: 10.0, peak: 100.0, every: 60.0, length: 5.0 }));
+
+        let path = std::env::temp_dir().join(format!("profile-trace-{}.csv", std::process::id()));
+        fs::write(&path, "seconds,tps\n30,20\n0,5\n").unwrap();
+        let trace = format!("trace:{}", path.display()).parse();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(trace, Ok(Curve::Trace(vec![(0.0, 5.0), (30.0, 20.0)])));
+    }
+
+    #[test]
+    fn rejects_malformed_profiles() {
+        let malformed =
+            ["constant", "constant:1,2", "ramp:1,2", "sine:5,1,0", "burst:1,2,0,1", "steps:", "steps:5", "zig:1"];
+        for profile in malformed {
+            assert!(profile.parse::<Curve>().is_err(), "{} parsed", profile);
+        }
+        assert!(parse_trace("seconds,tps\n").is_err());
+    }
+
+    #[test]
+    fn rejects_negative_and_non_finite_rates() {
+        let invalid =
+            ["constant:-1", "constant:NaN", "constant:inf", "ramp:0,inf,10", "steps:5@10,NaN@5", "burst:1,-5,9,1"];
+        for profile in invalid {
+            assert!(profile.parse::<Curve>().is_err(), "{} parsed", profile);
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use kaspa_consensus_core::tx::{TransactionId, TransactionOutpoint, TransactionOutput, UtxoEntry};
+use kaspa_notify::scope::{Scope, VirtualChainChangedScope, VirtualDaaScoreChangedScope};
+use kaspa_rpc_core::Notification;
+use kaspa_txscript::pay_to_address_script;
+use log::{debug, warn};
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::{
+    budget::Grant,
+    cli,
+    client_pool::{self, ClientPool},
+    config::Config,
+    rejection::{Rejection, RejectionCounts},
+    rpc,
+    shape::Shape,
+    spam::{self, percentile, Session},
+    tx::{self, format_kas},
+    tx_log,
+};
+
+/// Result of a version not sent because an earlier one was already accepted.
+const SKIPPED: &str = "skipped";
+/// Result of a version not sent because its fee would exceed the budget.
+const BUDGET_EXHAUSTED: &str = "fee budget exhausted";
+
+// ----------------------- options -----------------------
+/// Settings of `--rbf`, read from `--rbf-*` arguments.
+struct RbfOptions {
+    tests: usize,
+    /// Replacements submitted after the first version of each test
+    replacements: usize,
+    /// Wait before each replacement; the last one repeats
+    delays: Vec<Duration>,
+    /// Fee rate of the first version, in sompi per gram
+    start_fee_rate: f64,
+    /// Fee rate factor from one version to the next
+    fee_step: f64,
+    report: Option<String>,
+}
+
+impl RbfOptions {
+    fn from_args(config: &Config, args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let delays = match cli::arg_value(args, "--rbf-delays-ms") {
+            Some(list) => list
+                .split(',')
+                .map(|delay| delay.trim().parse::<u64>().map(Duration::from_millis))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid value for --rbf-delays-ms: {} ({})", list, e))?,
+            None => vec![Duration::from_secs(1)],
+        };
+        let fee_step = cli::parse_arg::<f64>(args, "--rbf-fee-step")?.unwrap_or(2.0);
+        if fee_step <= 1.0 {
+            return Err(format!("--rbf-fee-step must be greater than 1, got {}", fee_step).into());
+        }
+        Ok(RbfOptions {
+            tests: cli::parse_arg::<usize>(args, "--rbf-tests")?.unwrap_or(10).max(1),
+            replacements: cli::parse_arg::<usize>(args, "--rbf-replacements")?.unwrap_or(3).max(1),
+            delays: if delays.is_empty() { vec![Duration::from_secs(1)] } else { delays },
+            start_fee_rate: cli::parse_arg::<f64>(args, "--rbf-start-fee-rate")?
+                .unwrap_or(config.base_fee_rate as f64)
+                .max(config.base_fee_rate as f64),
+            fee_step,
+            report: cli::arg_value(args, "--report"),
+        })
+    }
+
+    fn delay(&self, replacement: usize) -> Duration {
+        self.delays[(replacement - 1).min(self.delays.len() - 1)]
+    }
+
+    fn fee_rate(&self, version: usize) -> f64 {
+        self.start_fee_rate * self.fee_step.powi(version as i32)
+    }
+}
+
+// ----------------------- acceptance -----------------------
+/// Acceptance times of the versions we submitted, filled in from
+/// VirtualChainChanged notifications.
+#[derive(Default)]
+struct Watch {
+    versions: Mutex<HashMap<TransactionId, Option<Instant>>>,
+}
+
+impl Watch {
+    fn add(&self, txid: TransactionId) {
+        self.versions.lock().unwrap().insert(txid, None);
+    }
+
+    fn accepted_at(&self, txid: &TransactionId) -> Option<Instant> {
+        self.versions.lock().unwrap().get(txid).copied().flatten()
+    }
+}
+
+async fn watch_loop(clients: Arc<ClientPool>, watch: Arc<Watch>) {
+    loop {
+        let subscription = clients
+            .call(|client| async move {
+                client.subscribe(Scope::VirtualDaaScoreChanged(VirtualDaaScoreChangedScope::default())).await?;
+                let notifications =
+                    client.subscribe(Scope::VirtualChainChanged(VirtualChainChangedScope::new(true))).await?;
+                Ok((client, notifications))
+            })
+            .await
+            .map_err(|e| e.to_string());
+        match subscription {
+            Ok((client, notifications)) => {
+                let reason = spam::follow_notifications(&*client, &notifications, |notification| {
+                    let Notification::VirtualChainChanged(changed) = notification else {
+                        return;
+                    };
+                    let now = Instant::now();
+                    let mut versions = watch.versions.lock().unwrap();
+                    for txid in
+                        changed.accepted_transaction_ids.iter().flat_map(|block| &block.accepted_transaction_ids)
+                    {
+                        if let Some(accepted_at) = versions.get_mut(txid) {
+                            accepted_at.get_or_insert(now);
+                        }
+                    }
+                })
+                .await;
+                warn!("VirtualChainChanged notifications {}, resubscribing", reason);
+            }
+            Err(e) => warn!("Failed to subscribe to VirtualChainChanged notifications: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+// ----------------------- tests -----------------------
+/// One row of the `--report` CSV: a version of a test transaction.
+#[derive(Debug, Serialize)]
+struct VersionRecord {
+    test: usize,
+    version: usize,
+    txid: String,
+    fee_rate: f64,
+    fee: u64,
+    submitted_at_ms: u128,
+    /// Round trip of the submit or replacement call
+    submit_ms: u128,
+    /// "submitted", "replaced <txid>", "skipped", "fee budget exhausted" or the node's
+    /// rejection message
+    result: String,
+    accepted: bool,
+}
+
+impl VersionRecord {
+    /// Whether the version reached the node, rejected or not.
+    fn was_sent(&self) -> bool {
+        self.result != SKIPPED && self.result != BUDGET_EXHAUSTED
+    }
+}
+
+/// What happened to one test transaction.
+struct TestOutcome {
+    versions: Vec<VersionRecord>,
+    /// Version the virtual chain accepted, with its submit-to-accept latency
+    accepted: Option<(usize, Duration)>,
+    /// Latest version the mempool took
+    latest: Option<usize>,
+}
+
+/// `--rbf`: sends a self-payment at a low fee rate, then replaces it with versions
+/// at increasing fee rates spending the same input, after `--rbf-delays-ms`. Records
+/// which version the virtual chain accepted, how the mempool answered each
+/// replacement and how long the calls and the acceptance took.
+pub async fn run(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = RbfOptions::from_args(config, args)?;
+    let session = spam::prepare(config, "🔁 Replace-by-fee test").await?;
+    spam::split(config, &session).await?;
+    let delays: Vec<String> = options.delays.iter().map(|delay| delay.as_millis().to_string()).collect();
+    println!(
+        "   RBF: {} tests, {} replacements each, fee rate {:.2} × {} per version, delays {}ms",
+        options.tests,
+        options.replacements,
+        options.start_fee_rate,
+        options.fee_step,
+        delays.join(",")
+    );
+
+    let watch = Arc::new(Watch::default());
+    let watcher = tokio::spawn(watch_loop(session.clients.clone(), watch.clone()));
+    let rejections = RejectionCounts::default();
+    let mut outcomes = Vec::with_capacity(options.tests);
+    for test in 1..=options.tests {
+        let outcome = run_test(config, &options, &session, &watch, &rejections, test).await?;
+        println!("   Test {:>3}: {}", test, describe(&outcome));
+        outcomes.push(outcome);
+    }
+    watcher.abort();
+
+    let replacements: Vec<&VersionRecord> = outcomes
+        .iter()
+        .flat_map(|outcome| &outcome.versions)
+        .filter(|record| record.version > 0 && record.was_sent())
+        .collect();
+    let taken = replacements.iter().filter(|record| record.result.starts_with("replaced")).count();
+    let mut replace_latencies: Vec<Duration> =
+        replacements.iter().map(|record| Duration::from_millis(record.submit_ms as u64)).collect();
+    let mut accept_latencies: Vec<Duration> =
+        outcomes.iter().filter_map(|outcome| outcome.accepted).map(|(_, lag)| lag).collect();
+    let mut by_version = vec![0; options.replacements + 1];
+    for (version, _) in outcomes.iter().filter_map(|outcome| outcome.accepted) {
+        by_version[version] += 1;
+    }
+    let latest_accepted = outcomes
+        .iter()
+        .filter(|outcome| outcome.accepted.is_some_and(|(version, _)| Some(version) == outcome.latest))
+        .count();
+
+    println!();
+    println!("🏁 Replace-by-fee finished");
+    println!(
+        "   Replacements: {} of {} taken by the mempool, call p50/p95 {}/{}ms",
+        taken,
+        replacements.len(),
+        percentile(&mut replace_latencies, 50.0).as_millis(),
+        percentile(&mut replace_latencies, 95.0).as_millis()
+    );
+    println!("   Rejections:   {}", rejections.describe());
+    let by_version: Vec<String> =
+        by_version.iter().enumerate().map(|(version, count)| format!("v{} {}", version, count)).collect();
+    println!("   Accepted:     {} ({})", accept_latencies.len(), by_version.join(", "));
+    println!("   Not accepted: {}", options.tests - accept_latencies.len());
+    println!("   Latest version accepted: {} of {} tests", latest_accepted, options.tests);
+    println!(
+        "   Acceptance:   p50 {:.1}s, p95 {:.1}s after the accepted version was submitted",
+        percentile(&mut accept_latencies, 50.0).as_secs_f64(),
+        percentile(&mut accept_latencies, 95.0).as_secs_f64()
+    );
+    println!("   Fees paid:    {}", session.budget.describe());
+    if let Some(path) = &options.report {
+        write_report(path, outcomes.iter().flat_map(|outcome| &outcome.versions))?;
+        println!("   Report:       {}", path);
+    }
+    Ok(())
+}
+
+/// Submits the versions of test transaction `test` and waits for one of them to be
+/// accepted. Every version pays the whole input minus its fee back to us.
+async fn run_test(
+    config: &Config,
+    options: &RbfOptions,
+    session: &Session,
+    watch: &Watch,
+    rejections: &RejectionCounts,
+    test: usize,
+) -> Result<TestOutcome, Box<dyn Error>> {
+    let shape = Shape::default();
+    let min_total = shape.min_input_total(options.fee_rate(options.replacements), 0, config.min_change_sompi);
+    let inputs = match session.pool.reserve(1, min_total) {
+        Some(inputs) => inputs,
+        None => {
+            spam::refresh(&session.clients, &session.address, &session.pool).await?;
+            session.pool.reserve(1, min_total).ok_or_else(|| {
+                format!("No spendable UTXO of at least {} left for test {}", format_kas(min_total), test)
+            })?
+        }
+    };
+    let outpoints: Vec<TransactionOutpoint> = inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+    let script_public_key = pay_to_address_script(&session.address);
+
+    let mut versions: Vec<VersionRecord> = Vec::new();
+    let mut submitted: Vec<(usize, TransactionId, Instant, u64)> = Vec::new();
+    let mut charged = 0;
+    // Grants of the submitted versions, each charging its fee above the ones before
+    let mut grants: Vec<(usize, Grant)> = Vec::new();
+    for version in 0..=options.replacements {
+        if version > 0 {
+            tokio::time::sleep(options.delay(version)).await;
+        }
+        let fee_rate = options.fee_rate(version);
+        let plan = shape.plan(&[inputs[0].1.amount], 0, fee_rate).ok_or("Input too small for the fee rate")?;
+        let output = TransactionOutput::new(plan.output_amounts[0], script_public_key.clone());
+        let tx = tx::build_signed_tx(session.keypair, &inputs, vec![output], vec![]);
+        let mut record = VersionRecord {
+            test,
+            version,
+            txid: tx.id().to_string(),
+            fee_rate,
+            fee: plan.fee,
+            submitted_at_ms: tx_log::unix_millis(),
+            submit_ms: 0,
+            result: SKIPPED.to_string(),
+            accepted: false,
+        };
+        // Once a version is in the chain, replacing it can only fail
+        if submitted.iter().any(|(_, txid, _, _)| watch.accepted_at(txid).is_some()) {
+            versions.push(record);
+            break;
+        }
+        let Ok(grant) = session.budget.try_spend(plan.fee.saturating_sub(charged)) else {
+            record.result = BUDGET_EXHAUSTED.to_string();
+            versions.push(record);
+            break;
+        };
+        watch.add(tx.id());
+        let started = Instant::now();
+        let tx = &tx;
+        let result = if version == 0 {
+            session
+                .clients
+                .call(|client| async move { rpc::submit_transaction(&*client, tx).await })
+                .await
+                .map(|_| "submitted".to_string())
+        } else {
+            session
+                .clients
+                .call(|client| async move { rpc::submit_transaction_replacement(&*client, tx).await })
+                .await
+                .map(|(_, replaced)| match submitted.iter().find(|(_, txid, _, _)| *txid == replaced) {
+                    Some((replaced_version, _, _, _)) => format!("replaced v{}", replaced_version),
+                    None => format!("replaced {}", replaced),
+                })
+        }
+        .map_err(|e| e.to_string());
+        record.submit_ms = started.elapsed().as_millis();
+        let result = match result {
+            Err(e) if Rejection::classify(&e) == Rejection::AlreadyInMempool => Ok("submitted".to_string()),
+            // Possibly on the node: watched like a submitted version so its inputs stay reserved
+            Err(e) if client_pool::is_unreachable(&e) => Ok(e),
+            result => result,
+        };
+        match result {
+            Ok(result) => {
+                debug!("Test {} v{}: {} at fee rate {:.2}", test, version, tx.id(), fee_rate);
+                charged = charged.max(plan.fee);
+                submitted.push((version, tx.id(), started, plan.fee));
+                grants.push((version, grant));
+                record.result = result;
+            }
+            Err(e) => {
+                session.budget.refund(grant);
+                rejections.record(Rejection::classify(&e));
+                record.result = e;
+            }
+        }
+        versions.push(record);
+        if submitted.is_empty() {
+            break;
+        }
+    }
+
+    // Wait for the virtual chain to accept one of the versions
+    let started = Instant::now();
+    let timeout = Duration::from_secs(config.acceptance_timeout_secs);
+    let mut accepted = None;
+    while !submitted.is_empty() && started.elapsed() < timeout {
+        accepted = submitted.iter().find_map(|(version, txid, submitted_at, fee)| {
+            watch.accepted_at(txid).map(|accepted_at| (*version, *txid, accepted_at - *submitted_at, *fee))
+        });
+        if accepted.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    match accepted {
+        Some((version, txid, _, fee)) => {
+            // Fee rates rise with each version, so the later ones charged the difference
+            for (_, grant) in grants.into_iter().filter(|(later, _)| *later > version) {
+                session.budget.refund(grant);
+            }
+            versions[version].accepted = true;
+            let amount = inputs[0].1.amount - fee;
+            let change = vec![(TransactionOutpoint::new(txid, 0), UtxoEntry::new(amount, script_public_key, 0, false))];
+            session.pool.mark_submitted(txid, &outpoints, change);
+        }
+        None if submitted.is_empty() => session.pool.release(&outpoints),
+        None => {
+            // The inputs stay reserved under the latest version, the one the mempool holds
+            let (_, txid, _, _) = submitted.last().unwrap();
+            session.pool.mark_submitted(*txid, &outpoints, Vec::new());
+        }
+    }
+    Ok(TestOutcome {
+        versions,
+        accepted: accepted.map(|(version, _, lag, _)| (version, lag)),
+        latest: submitted.last().map(|(version, _, _, _)| *version),
+    })
+}
+
+/// E.g. `v0 → v1 → v2 (fee too low) → v3, accepted v3 after 1.2s`.
+fn describe(outcome: &TestOutcome) -> String {
+    let versions: Vec<String> = outcome
+        .versions
+        .iter()
+        .map(|record| {
+            if record.result == "submitted" || record.result.starts_with("replaced") {
+                format!("v{}", record.version)
+            } else if !record.was_sent() {
+                format!("v{} ({})", record.version, record.result)
+            } else {
+                format!("v{} ({})", record.version, Rejection::classify(&record.result))
+            }
+        })
+        .collect();
+    let accepted = match outcome.accepted {
+        Some((version, lag)) => format!("accepted v{} after {:.1}s", version, lag.as_secs_f64()),
+        None => "none accepted".to_string(),
+    };
+    format!("{}, {}", versions.join(" → "), accepted)
+}
+
+fn write_report<'a>(path: &str, records: impl Iterator<Item = &'a VersionRecord>) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| format!("Cannot create {}: {}", path, e))?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::{fmt, str::FromStr, sync::Mutex, time::Duration};
+
+use kaspa_consensus_core::tx::TransactionOutpoint;
+use tokio::time::Instant;
+
+/// First pause after the node reports a full mempool.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest pause while the mempool stays full.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// ----------------------- classification -----------------------
+/// Why the node rejected a transaction, parsed from the RPC error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rejection {
+    /// An input is unknown to the node, e.g. its parent has not reached it yet
+    Orphan,
+    /// An input is already spent by another mempool transaction
+    DoubleSpend,
+    FeeTooLow,
+    MassTooLarge,
+    MempoolFull,
+    /// The same transaction was submitted before, e.g. by a requeued request
+    AlreadyInMempool,
+    Other,
+}
+
+impl Rejection {
+    pub const ALL: [Rejection; 7] = [
+        Rejection::Orphan,
+        Rejection::DoubleSpend,
+        Rejection::FeeTooLow,
+        Rejection::MassTooLarge,
+        Rejection::MempoolFull,
+        Rejection::AlreadyInMempool,
+        Rejection::Other,
+    ];
+
+    /// Matches the wording of the node's mempool rule errors.
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let has = |needle: &str| message.contains(needle);
+        if has("already in the mempool") || has("already have transaction") || has("duplicate") {
+            Rejection::AlreadyInMempool
+        } else if has("orphan") || has("missing outpoint") {
+            Rejection::Orphan
+        } else if has("already spent") || has("double spend") {
+            Rejection::DoubleSpend
+        } else if has("under the required amount") || has("insufficient fee") || has("fee rate") || has("feerate") {
+            Rejection::FeeTooLow
+        } else if has("mass") && (has("larger than") || has("exceeds") || has("too large")) {
+            Rejection::MassTooLarge
+        } else if has("mempool is full") || has("mempool full") || has("because it's full") {
+            Rejection::MempoolFull
+        } else {
+            Rejection::Other
+        }
+    }
+
+    fn index(self) -> usize {
+        Rejection::ALL.iter().position(|&rejection| rejection == self).unwrap()
+    }
+}
+
+/// The `outpoints` a double spend rejection names as already spent, in either the
+/// `(txid, index)` or `txid:index` form. Empty if the message names none of them.
+pub fn double_spent(message: &str, outpoints: &[TransactionOutpoint]) -> Vec<TransactionOutpoint> {
+    outpoints
+        .iter()
+        .filter(|outpoint| {
+            message.contains(&outpoint.to_string())
+                || message.contains(&format!("{}:{}", outpoint.transaction_id, outpoint.index))
+        })
+        .copied()
+        .collect()
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Rejection::Orphan => "orphan",
+            Rejection::DoubleSpend => "double spend",
+            Rejection::FeeTooLow => "fee too low",
+            Rejection::MassTooLarge => "mass too large",
+            Rejection::MempoolFull => "mempool full",
+            Rejection::AlreadyInMempool => "already in mempool",
+            Rejection::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses the kinds of `--mock-reject-kind`.
+impl FromStr for Rejection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "orphan" => Ok(Rejection::Orphan),
+            "double-spend" => Ok(Rejection::DoubleSpend),
+            "fee" => Ok(Rejection::FeeTooLow),
+            "mass" => Ok(Rejection::MassTooLarge),
+            "mempool-full" => Ok(Rejection::MempoolFull),
+            "duplicate" => Ok(Rejection::AlreadyInMempool),
+            "other" => Ok(Rejection::Other),
+            _ => Err(format!(
+                "Unknown rejection kind: {}. Supported: orphan, double-spend, fee, mass, mempool-full, duplicate, other",
+                s
+            )),
+        }
+    }
+}
+
+// ----------------------- counts -----------------------
+/// Rejections seen per category, including those a resubmit recovered from.
+#[derive(Default)]
+pub struct RejectionCounts {
+    counts: Mutex<[u64; Rejection::ALL.len()]>,
+}
+
+impl RejectionCounts {
+    pub fn record(&self, rejection: Rejection) {
+        self.counts.lock().unwrap()[rejection.index()] += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.lock().unwrap().iter().sum()
+    }
+
+    /// Non-zero categories, e.g. `orphan 3, fee too low 1`.
+    pub fn describe(&self) -> String {
+        let counts = self.counts.lock().unwrap();
+        let parts: Vec<String> = Rejection::ALL
+            .iter()
+            .zip(counts.iter())
+            .filter(|(_, &count)| count > 0)
+            .map(|(rejection, count)| format!("{} {}", rejection, count))
+            .collect();
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+// ----------------------- backoff -----------------------
+/// Pause the send loop keeps while the node reports a full mempool. If the mempool
+/// is still full after a pause, the next one is twice as long, up to `MAX_BACKOFF`;
+/// an accepted submission resets it.
+#[derive(Default)]
+pub struct Backoff {
+    state: Mutex<(Option<Instant>, Duration)>,
+}
+
+impl Backoff {
+    /// Starts or extends the pause and returns its length.
+    pub fn hit(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (until, delay) = &mut *state;
+        let now = Instant::now();
+        if until.is_some_and(|until| until > now) {
+            return *delay;
+        }
+        *delay = if until.is_some() { (*delay * 2).min(MAX_BACKOFF) } else { INITIAL_BACKOFF };
+        *until = Some(now + *delay);
+        *delay
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = (None, Duration::ZERO);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.lock().unwrap().0.is_some_and(|until| until > Instant::now())
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use kaspa_consensus_core::tx::{TransactionOutpoint, TransactionOutput, UtxoEntry};
+use log::{debug, info, warn};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    budget::FeeBudget,
+    client_pool::{self, ClientPool},
+    config::Config,
+    fees::FeeEstimator,
+    rejection::Rejection,
+    rpc,
+    spam::Stats,
+    tx::{self, format_kas},
+    utxo::Utxo,
+    wallets::Wallet,
+};
+
+/// Split transactions sent by the replenisher and the UTXOs they created.
+#[derive(Default)]
+pub struct ReplenishCounts {
+    splits: AtomicU64,
+    outputs: AtomicU64,
+}
+
+impl ReplenishCounts {
+    fn record(&self, outputs: usize) {
+        self.splits.fetch_add(1, Ordering::Relaxed);
+        self.outputs.fetch_add(outputs as u64, Ordering::Relaxed);
+    }
+
+    pub fn splits(&self) -> u64 {
+        self.splits.load(Ordering::Relaxed)
+    }
+
+    pub fn outputs(&self) -> u64 {
+        self.outputs.load(Ordering::Relaxed)
+    }
+}
+
+/// Keeps every sender above `REPLENISH_LOW_WATER` spendable UTXOs during long runs by
+/// splitting one of its large outputs into `AMOUNT_PER_UTXO` outputs, with one split
+/// in flight per sender. An idle large output is reserved directly; as the send
+/// loop spends the largest outputs first, a sender below the mark also asks it to
+/// hand over its next large output through `offer`.
+pub struct Replenisher {
+    low_water: usize,
+    amount_per_utxo: u64,
+    outputs_per_transaction: usize,
+    min_change_sompi: u64,
+    /// Smallest output worth splitting: two outputs, with `MIN_CHANGE_SOMPI` towards the fee
+    min_amount: u64,
+    interval: Duration,
+    clients: Arc<ClientPool>,
+    senders: Vec<Wallet>,
+    budget: Arc<FeeBudget>,
+    fees: Arc<FeeEstimator>,
+    /// Per sender: the send loop should hand over its next large output
+    wanted: Vec<AtomicBool>,
+    handoff: UnboundedSender<(usize, Utxo)>,
+}
+
+impl Replenisher {
+    pub fn new(
+        config: &Config,
+        clients: Arc<ClientPool>,
+        senders: Vec<Wallet>,
+        budget: Arc<FeeBudget>,
+        fees: Arc<FeeEstimator>,
+    ) -> (Arc<Self>, UnboundedReceiver<(usize, Utxo)>) {
+        let (handoff, handoffs) = mpsc::unbounded_channel();
+        let replenisher = Replenisher {
+            low_water: config.replenish_low_water,
+            amount_per_utxo: config.amount_per_utxo,
+            outputs_per_transaction: config.outputs_per_transaction.max(2),
+            min_change_sompi: config.min_change_sompi,
+            min_amount: 2 * config.amount_per_utxo + config.min_change_sompi,
+            interval: Duration::from_secs(config.utxo_refresh_secs.max(1)),
+            wanted: senders.iter().map(|_| AtomicBool::new(false)).collect(),
+            clients,
+            senders,
+            budget,
+            fees,
+            handoff,
+        };
+        (Arc::new(replenisher), handoffs)
+    }
+
+    /// Called by the send loop with the inputs it reserved from sender `from`. Takes
+    /// a single large input over for a split if that sender asked for one.
+    pub fn offer(&self, from: usize, inputs: &[Utxo]) -> bool {
+        let [utxo] = inputs else { return false };
+        if utxo.1.amount < self.min_amount || !self.wanted[from].swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        self.handoff.send((from, utxo.clone())).is_ok()
+    }
+
+    /// Checks the senders every `UTXO_REFRESH_SECS` and splits the outputs reserved
+    /// or handed over for them, until aborted.
+    pub async fn run(self: Arc<Self>, mut handoffs: UnboundedReceiver<(usize, Utxo)>, stats: Arc<Stats>) {
+        let mut in_flight: Vec<Option<TransactionOutpoint>> = vec![None; self.senders.len()];
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    for (from, sender) in self.senders.iter().enumerate() {
+                        if in_flight[from].is_some_and(|outpoint| sender.pool.is_pending(&outpoint)) {
+                            continue;
+                        }
+                        in_flight[from] = None;
+                        let below = sender.pool.stats().available < self.low_water;
+                        let idle = if below { sender.pool.reserve(1, self.min_amount) } else { None };
+                        match idle {
+                            Some(mut inputs) => in_flight[from] = self.split(from, inputs.remove(0), &stats).await,
+                            None => self.wanted[from].store(below, Ordering::Relaxed),
+                        }
+                    }
+                }
+                Some((from, utxo)) = handoffs.recv() => {
+                    in_flight[from] = self.split(from, utxo, &stats).await;
+                }
+            }
+        }
+    }
+
+    /// Splits `utxo` of sender `from` into as many `AMOUNT_PER_UTXO` outputs as it
+    /// funds, up to `OUTPUTS_PER_TRANSACTION`, paying the change back. Returns an
+    /// outpoint of the split, pending until its outputs are spendable.
+    async fn split(&self, from: usize, utxo: Utxo, stats: &Stats) -> Option<TransactionOutpoint> {
+        let sender = &self.senders[from];
+        let outpoints = [utxo.0];
+        let input_amount = utxo.1.amount;
+        let fee_rate = self.fees.rate();
+        let fits = |num_outputs: usize| {
+            let mass = tx::splitting_mass(input_amount, self.amount_per_utxo, num_outputs);
+            let spent = self.amount_per_utxo * num_outputs as u64 + tx::required_fee(fee_rate, mass);
+            mass <= tx::MAXIMUM_STANDARD_TRANSACTION_MASS && spent <= input_amount
+        };
+        let Some(num_outputs) = (2..=self.outputs_per_transaction).rev().find(|&num_outputs| fits(num_outputs)) else {
+            debug!("{} is too small to replenish from", format_kas(input_amount));
+            sender.pool.release(&outpoints);
+            return None;
+        };
+        let fee = tx::required_fee_splitting(fee_rate, input_amount, self.amount_per_utxo, num_outputs);
+        let mut outputs: Vec<TransactionOutput> = (0..num_outputs)
+            .map(|_| TransactionOutput::new(self.amount_per_utxo, sender.script_public_key.clone()))
+            .collect();
+        // Change below MIN_CHANGE_SOMPI is left to the miner rather than creating dust
+        let change = input_amount - self.amount_per_utxo * num_outputs as u64 - fee;
+        if change >= self.min_change_sompi {
+            outputs.push(TransactionOutput::new(change, sender.script_public_key.clone()));
+        }
+        let paid = input_amount - outputs.iter().map(|output| output.value).sum::<u64>();
+        let Ok(grant) = self.budget.try_spend(paid) else {
+            debug!("Fee budget refused a replenishing split");
+            sender.pool.release(&outpoints);
+            return None;
+        };
+
+        let tx = tx::build_signed_tx(sender.keypair, &[utxo], outputs, vec![]);
+        let signed = &tx;
+        let submitted = self
+            .clients
+            .call(|client| async move { rpc::submit_transaction(&*client, signed).await })
+            .await
+            .map_err(|e| e.to_string());
+        let txid = match submitted {
+            Ok(txid) => txid,
+            Err(e) if Rejection::classify(&e) == Rejection::AlreadyInMempool => tx.id(),
+            Err(e) if client_pool::is_unreachable(&e) => {
+                warn!("Replenishing split {} may not have reached the node: {}", tx.id(), e);
+                sender.pool.mark_submitted(tx.id(), &outpoints, Vec::new());
+                return None;
+            }
+            Err(e) => {
+                warn!("Replenishing split {} was rejected: {}", tx.id(), e);
+                self.budget.refund(grant);
+                sender.pool.release(&outpoints);
+                return None;
+            }
+        };
+        let created = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let entry = UtxoEntry::new(output.value, output.script_public_key.clone(), 0, false);
+                (TransactionOutpoint::new(txid, index as u32), entry)
+            })
+            .collect();
+        sender.pool.mark_submitted(txid, &outpoints, created);
+        stats.replenished.record(num_outputs);
+        info!("Replenished {} with {} outputs, fee {}", String::from(&sender.address), num_outputs, format_kas(paid));
+        Some(TransactionOutpoint::new(txid, 0))
+    }
+}
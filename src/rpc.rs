@@ -0,0 +1,116 @@
+use std::{cmp::Reverse, collections::HashMap, error::Error, sync::Arc};
+
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{Transaction, TransactionId};
+use log::info;
+
+use crate::{
+    backend::{self, Encoding, RpcBackend},
+    utxo::{Drift, Utxo, UtxoPool},
+    wallets::Wallet,
+};
+
+pub async fn connect(url: &str, encoding: Encoding) -> Result<Arc<dyn RpcBackend>, Box<dyn Error>> {
+    let client = backend::connect(url, encoding).await.map_err(|e| format!("Failed to connect to {}: {}", url, e))?;
+    info!("Connected to {} over {}", url, client.transport());
+    Ok(client)
+}
+
+/// Fetches every UTXO of `address`, largest first.
+pub async fn fetch_utxos(client: &dyn RpcBackend, address: &Address) -> Result<Vec<Utxo>, Box<dyn Error>> {
+    let entries = client.api().get_utxos_by_addresses(vec![address.clone()]).await?;
+    let mut utxos: Vec<Utxo> =
+        entries.into_iter().map(|entry| (entry.outpoint.into(), entry.utxo_entry.into())).collect();
+    utxos.sort_by_key(|(_, entry)| Reverse(entry.amount));
+    Ok(utxos)
+}
+
+pub async fn virtual_daa_score(client: &dyn RpcBackend) -> Result<u64, Box<dyn Error>> {
+    Ok(client.api().get_block_dag_info().await?.virtual_daa_score)
+}
+
+/// Replaces `pool`'s view with the node's current UTXO set for `address`.
+pub async fn refresh_pool(client: &dyn RpcBackend, address: &Address, pool: &UtxoPool) -> Result<(), Box<dyn Error>> {
+    let virtual_daa_score = virtual_daa_score(client).await?;
+    pool.begin_snapshot();
+    let utxos = match fetch_utxos(client, address).await {
+        Ok(utxos) => utxos,
+        Err(e) => {
+            pool.cancel_snapshot();
+            return Err(e);
+        }
+    };
+    pool.update_from_node(utxos, virtual_daa_score);
+    Ok(())
+}
+
+/// Refreshes the pools of all `wallets` from a single UTXO request. Returns how
+/// far they had drifted from the node's view.
+pub async fn refresh_wallets(client: &dyn RpcBackend, wallets: &[Wallet]) -> Result<Drift, Box<dyn Error>> {
+    let addresses = wallets.iter().map(|wallet| wallet.address.clone()).collect();
+    // The score predates the snapshot, so outputs it reports can only look less mature
+    let virtual_daa_score = virtual_daa_score(client).await?;
+    for wallet in wallets {
+        wallet.pool.begin_snapshot();
+    }
+    let entries = match client.api().get_utxos_by_addresses(addresses).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            for wallet in wallets {
+                wallet.pool.cancel_snapshot();
+            }
+            return Err(e.into());
+        }
+    };
+    let mut by_script: HashMap<_, Vec<Utxo>> = HashMap::new();
+    for entry in entries {
+        let utxo: Utxo = (entry.outpoint.into(), entry.utxo_entry.into());
+        by_script.entry(utxo.1.script_public_key.clone()).or_default().push(utxo);
+    }
+    let drift = wallets
+        .iter()
+        .map(|wallet| {
+            let utxos = by_script.remove(&wallet.script_public_key).unwrap_or_default();
+            wallet.pool.update_from_node(utxos, virtual_daa_score)
+        })
+        .sum();
+    Ok(drift)
+}
+
+/// Whether the node holds `txid` in its mempool, orphan pool included.
+pub async fn is_in_mempool(client: &dyn RpcBackend, txid: TransactionId) -> Result<bool, Box<dyn Error>> {
+    match client.api().get_mempool_entry(txid, true, false).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.to_string().contains("not found") => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns to `pool` the inputs of stale submissions the node no longer holds in
+/// its mempool while still reporting the inputs unspent. Returns how many inputs
+/// were released.
+pub async fn release_abandoned(client: &dyn RpcBackend, pool: &UtxoPool) -> Result<usize, Box<dyn Error>> {
+    let mut released = 0;
+    for txid in pool.stale_submissions() {
+        if !is_in_mempool(client, txid).await? {
+            released += pool.release_abandoned(&txid);
+        }
+    }
+    Ok(released)
+}
+
+pub async fn submit_transaction(client: &dyn RpcBackend, tx: &Transaction) -> Result<TransactionId, Box<dyn Error>> {
+    Ok(client.api().submit_transaction(tx.into(), false).await?)
+}
+
+/// Submits `tx` as a replacement of the mempool transaction it double spends.
+/// Returns its id and the id of the transaction it replaced.
+pub async fn submit_transaction_replacement(
+    client: &dyn RpcBackend,
+    tx: &Transaction,
+) -> Result<(TransactionId, TransactionId), Box<dyn Error>> {
+    let response = client.api().submit_transaction_replacement(tx.into()).await?;
+    let replaced = Transaction::try_from(response.replaced_transaction)
+        .map_err(|e| format!("Invalid replaced transaction from the node: {}", e))?;
+    Ok((response.transaction_id, replaced.id()))
+}
@@ -0,0 +1,346 @@
+use std::{error::Error, fmt::Display, fs, str::FromStr, sync::Arc, time::Duration};
+
+use log::warn;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    config::Config,
+    consolidate,
+    distribution::Distribution,
+    fees::{FeeEstimator, FeeTier},
+    profile::Curve,
+    scheduler::{Scheduler, SystemClock},
+    shape::ShapeMix,
+    spam::{self, SendEnd, Sender, Session},
+    tx::format_kas,
+};
+
+/// Fewest finished transactions before `max_reject_pct` is judged.
+const MIN_REJECT_SAMPLE: u64 = 100;
+
+// ----------------------- format -----------------------
+/// A multi-phase run read from a TOML file (`--scenario FILE`). Settings a phase
+/// does not override come from the environment, as in a normal run.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: Option<String>,
+    /// Fee limits across all phases
+    #[serde(default)]
+    pub budget: Budget,
+    #[serde(rename = "phase", default)]
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Budget {
+    pub max_total_fees_sompi: Option<u64>,
+    pub max_fees_per_minute_sompi: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Phase {
+    /// Split UTXOs until the pool holds `utxos`
+    Split(SplitPhase),
+    /// Send transactions at a rate or profile
+    Send(SendPhase),
+    /// Send nothing for a while, e.g. to let the mempool drain
+    Pause(PausePhase),
+    /// Merge our UTXOs, or sweep them to another address
+    Consolidate(ConsolidatePhase),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitPhase {
+    pub name: Option<String>,
+    pub utxos: usize,
+    pub amount_per_utxo: Option<u64>,
+    pub outputs_per_transaction: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendPhase {
+    pub name: Option<String>,
+    pub duration_secs: Option<u64>,
+    pub tps: Option<u64>,
+    /// `TPS_PROFILE` syntax; overrides `tps`
+    #[serde(default, deserialize_with = "parsed")]
+    pub profile: Option<Curve>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub shapes: Option<ShapeMix>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub fee_tier: Option<FeeTier>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub fee_rate_dist: Option<Distribution>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub payload_size_dist: Option<Distribution>,
+    #[serde(default)]
+    pub stop: StopConditions,
+}
+
+/// Ends a send phase early. `max_transactions` and `max_fees_sompi` complete the
+/// phase; `max_failed` and `max_reject_pct` abort the whole scenario.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StopConditions {
+    pub max_transactions: Option<u64>,
+    pub max_fees_sompi: Option<u64>,
+    pub max_failed: Option<u64>,
+    pub max_reject_pct: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PausePhase {
+    pub name: Option<String>,
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConsolidatePhase {
+    pub name: Option<String>,
+    pub to: Option<String>,
+    pub sweep_to: Option<String>,
+    pub max_inputs: Option<usize>,
+}
+
+/// Deserializes an optional string through the type's `FromStr`, so scenario files
+/// use the same syntax as the environment.
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?.map(|s| s.parse().map_err(serde::de::Error::custom)).transpose()
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read scenario {}: {}", path, e))?;
+        let scenario: Scenario = toml::from_str(&contents).map_err(|e| format!("Invalid scenario {}: {}", path, e))?;
+        if scenario.phases.is_empty() {
+            return Err(format!("Scenario {} has no [[phase]] entries", path).into());
+        }
+        for (index, phase) in scenario.phases.iter().enumerate() {
+            if let Phase::Send(send) = phase {
+                let bounded = send.duration_secs.is_some_and(|secs| secs > 0)
+                    || send.stop.max_transactions.is_some()
+                    || send.stop.max_fees_sompi.is_some();
+                if !bounded {
+                    return Err(format!(
+                        "Phase {} ({}) never ends: set duration_secs, stop.max_transactions or stop.max_fees_sompi",
+                        index + 1,
+                        phase.name()
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+impl Phase {
+    fn name(&self) -> String {
+        let (name, kind) = match self {
+            Phase::Split(phase) => (&phase.name, "split"),
+            Phase::Send(phase) => (&phase.name, "send"),
+            Phase::Pause(phase) => (&phase.name, "pause"),
+            Phase::Consolidate(phase) => (&phase.name, "consolidate"),
+        };
+        name.clone().unwrap_or_else(|| kind.to_string())
+    }
+}
+
+impl SendPhase {
+    /// `base` with this phase's overrides applied.
+    fn config(&self, base: &Config) -> Config {
+        let mut config = base.clone();
+        config.spam_duration_seconds = self.duration_secs.unwrap_or(0);
+        if let Some(tps) = self.tps {
+            config.target_tps = tps;
+            config.tps_profile = None;
+        }
+        if self.profile.is_some() {
+            config.tps_profile = self.profile.clone();
+        }
+        if let Some(shapes) = &self.shapes {
+            config.tx_shapes = shapes.clone();
+        }
+        if let Some(tier) = self.fee_tier {
+            config.fee_tier = tier;
+        }
+        if self.fee_rate_dist.is_some() {
+            config.fee_rate_distribution = self.fee_rate_dist.clone();
+        }
+        if self.payload_size_dist.is_some() {
+            config.payload_size_distribution = self.payload_size_dist.clone();
+        }
+        config
+    }
+}
+
+/// Why a stop condition fired.
+struct Stop {
+    reason: String,
+    abort: bool,
+}
+
+impl StopConditions {
+    fn check(&self, submitted: u64, failed: u64, fees: u64) -> Option<Stop> {
+        let done = |reason: String| Some(Stop { reason, abort: false });
+        let abort = |reason: String| Some(Stop { reason, abort: true });
+        if let Some(max) = self.max_failed.filter(|max| failed > *max) {
+            return abort(format!("more than {} transactions failed", max));
+        }
+        if let Some(max) = self.max_reject_pct {
+            let finished = submitted + failed;
+            if finished >= MIN_REJECT_SAMPLE && failed as f64 * 100.0 / finished as f64 > max {
+                return abort(format!("rejection rate above {}%", max));
+            }
+        }
+        if let Some(max) = self.max_transactions.filter(|max| submitted >= *max) {
+            return done(format!("{} transactions submitted", max));
+        }
+        if let Some(max) = self.max_fees_sompi.filter(|max| fees >= *max) {
+            return done(format!("{} spent", format_kas(max)));
+        }
+        None
+    }
+}
+
+// ----------------------- runner -----------------------
+/// `--scenario FILE`: runs the scenario's phases in order on one connection, UTXO
+/// pool and fee budget.
+pub async fn run(config: &Config, path: &str) -> Result<(), Box<dyn Error>> {
+    let scenario = Scenario::load(path)?;
+    let mut config = config.clone();
+    if let Some(limit) = scenario.budget.max_total_fees_sompi {
+        config.max_total_fees_sompi = limit;
+    }
+    if let Some(limit) = scenario.budget.max_fees_per_minute_sompi {
+        config.max_fees_per_minute_sompi = limit;
+    }
+    let title = format!("🎬 Scenario {}", scenario.name.as_deref().unwrap_or(path));
+    let session = spam::prepare(&config, &title).await?;
+    println!("   Phases:  {}", scenario.phases.iter().map(Phase::name).collect::<Vec<_>>().join(" → "));
+
+    let mut results = Vec::new();
+    for (index, phase) in scenario.phases.iter().enumerate() {
+        println!();
+        println!("▶️  Phase {}/{}: {}", index + 1, scenario.phases.len(), phase.name());
+        let fees_before = session.budget.spent();
+        let outcome = match phase {
+            Phase::Split(split) => {
+                let mut phase_config = config.clone();
+                phase_config.target_utxo_count = split.utxos;
+                phase_config.amount_per_utxo = split.amount_per_utxo.unwrap_or(config.amount_per_utxo);
+                phase_config.outputs_per_transaction =
+                    split.outputs_per_transaction.unwrap_or(config.outputs_per_transaction);
+                spam::split(&phase_config, &session).await?;
+                Outcome::Completed(format!("{} spendable UTXOs", session.pool_stats().available))
+            }
+            Phase::Send(send) => send_phase(&config, &session, send).await?,
+            Phase::Pause(pause) => {
+                tokio::time::sleep(Duration::from_secs(pause.duration_secs)).await;
+                Outcome::Completed(format!("paused {}s", pause.duration_secs))
+            }
+            Phase::Consolidate(consolidation) => {
+                let options = consolidate::Options {
+                    to: consolidation.to.clone(),
+                    sweep_to: consolidation.sweep_to.clone(),
+                    max_inputs: consolidation.max_inputs,
+                };
+                consolidate::consolidate(&config, &options, &session.budget).await?;
+                spam::refresh(&session.clients, &session.address, &session.pool).await?;
+                Outcome::Completed(format!("{} UTXOs left", session.pool.stats().available))
+            }
+        };
+        let fees = session.budget.spent() - fees_before;
+        let stop = matches!(outcome, Outcome::Aborted(_) | Outcome::BudgetExhausted);
+        results.push((phase.name(), outcome, fees));
+        if stop {
+            break;
+        }
+    }
+
+    println!();
+    println!("🏁 Scenario finished");
+    for (name, outcome, fees) in &results {
+        println!("   {:<16} {} (fees {})", name, outcome, format_kas(*fees));
+    }
+    let skipped = scenario.phases.len() - results.len();
+    if skipped > 0 {
+        println!("   {} phases skipped", skipped);
+    }
+    println!("   Fees paid:       {}", session.budget.describe());
+    match results.last() {
+        Some((name, Outcome::Aborted(reason), _)) => {
+            Err(format!("Phase {} aborted the scenario: {}", name, reason).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+enum Outcome {
+    Completed(String),
+    Aborted(String),
+    BudgetExhausted,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Completed(summary) => write!(f, "✅ {}", summary),
+            Outcome::Aborted(reason) => write!(f, "❌ aborted: {}", reason),
+            Outcome::BudgetExhausted => write!(f, "💸 fee budget reached"),
+        }
+    }
+}
+
+async fn send_phase(base: &Config, session: &Session, phase: &SendPhase) -> Result<Outcome, Box<dyn Error>> {
+    let config = phase.config(base);
+    // Each phase follows its own fee tier
+    let fees = Arc::new(FeeEstimator::new(config.fee_tier, config.base_fee_rate));
+    let estimator = &fees;
+    if let Err(e) = session.clients.call(|client| async move { estimator.refresh(&*client).await }).await {
+        warn!("No fee estimate from the node, paying BASE_FEE_RATE: {}", e);
+    }
+    let session = Session { fees, ..session.clone() };
+    spam::refresh_wallets(&session.clients, &session.senders()).await?;
+
+    let profile = config.traffic_profile();
+    println!("   Sending: {}", profile);
+    let mut sender = Sender::start(&config, &session);
+    let stats = sender.stats().clone();
+    let mut scheduler = Scheduler::new(SystemClock::new(), profile, Duration::from_millis(config.millis_per_tick));
+    let duration = (config.spam_duration_seconds > 0).then(|| Duration::from_secs(config.spam_duration_seconds));
+    let fees_before = session.budget.spent();
+    let mut fired = None;
+    let end = sender
+        .send_until(&config, &mut scheduler, duration, || {
+            fired = phase.stop.check(stats.submitted(), stats.failed(), session.budget.spent() - fees_before);
+            fired.as_ref().map(|stop| stop.reason.clone())
+        })
+        .await;
+    sender.finish().await;
+
+    let mut summary = format!("{} submitted", stats.submitted());
+    if stats.acceptance.is_active() {
+        let acceptance = stats.acceptance.summary();
+        summary += &format!(", {} accepted (p99 {:.1}s)", acceptance.accepted, acceptance.p99.as_secs_f64());
+    }
+    summary += &format!(", {} failed, {:.1} TPS", stats.failed(), scheduler.achieved_tps());
+    Ok(match (end, fired) {
+        (SendEnd::BudgetExhausted, _) => Outcome::BudgetExhausted,
+        (SendEnd::Stopped(_), Some(stop)) if stop.abort => Outcome::Aborted(format!("{} ({})", stop.reason, summary)),
+        (SendEnd::Stopped(reason), _) => Outcome::Completed(format!("{}, stopped: {}", summary, reason)),
+        (SendEnd::Deadline, _) => Outcome::Completed(summary),
+    })
+}
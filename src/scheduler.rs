@@ -0,0 +1,186 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::profile::Profile;
+
+/// Most ticks' worth of quota we accrue at once. A stalled loop (slow signing, a
+/// blocked runtime) resumes at the target rate instead of bursting to catch up.
+const MAX_CATCH_UP_TICKS: u32 = 2;
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+/// Target rates are applied in thousandths of a transaction per second.
+const MILLI: f64 = 1000.0;
+
+// ----------------------- clocks -----------------------
+/// Source of elapsed time for the scheduler.
+pub trait Clock {
+    /// Time elapsed since the clock was created.
+    fn elapsed(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Manually advanced clock, so pacing can be simulated deterministically.
+/// Clones share the same time.
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+// ----------------------- scheduler -----------------------
+/// Paces the send loop along a traffic profile. Each tick accrues a fractional
+/// quota of `target_tps * elapsed`, with the target read from the profile at the
+/// time of the tick; whole transactions are released and the remainder is carried
+/// to the next tick. The quota is kept in milli-transaction-nanoseconds so that
+/// pacing at a constant rate is exact over any run length.
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    profile: Profile,
+    tick: Duration,
+    quota: u128,
+    started: Duration,
+    last_tick: Duration,
+    sent: u64,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C, profile: Profile, tick: Duration) -> Self {
+        let started = clock.elapsed();
+        let tick = tick.max(Duration::from_millis(1));
+        Scheduler { clock, profile, tick, quota: 0, started, last_tick: started, sent: 0 }
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        self.tick
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Returns how many transactions may be sent now.
+    pub fn next_tick(&mut self) -> u64 {
+        let now = self.clock.elapsed();
+        let elapsed = now.saturating_sub(self.last_tick).min(self.tick * MAX_CATCH_UP_TICKS);
+        self.last_tick = now;
+        let milli_tps = (self.profile.tps_at(now.saturating_sub(self.started)) * MILLI).round() as u128;
+        self.quota += milli_tps * elapsed.as_nanos();
+        let unit = NANOS_PER_SECOND * MILLI as u128;
+        let due = self.quota / unit;
+        self.quota %= unit;
+        due as u64
+    }
+
+    /// Records transactions actually sent, which may be fewer than released when
+    /// the UTXO pool runs dry. Unused quota is not carried over.
+    pub fn record_sent(&mut self, count: u64) {
+        self.sent += count;
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Average rate since the scheduler was created.
+    pub fn achieved_tps(&self) -> f64 {
+        let elapsed = self.clock.elapsed().saturating_sub(self.started).as_secs_f64();
+        if elapsed > 0.0 {
+            self.sent as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Simulates `duration` of pacing on a virtual clock with a perfectly regular tick
+/// (the last one cut short to end exactly at `duration`) and returns the number of
+/// transactions released.
+pub fn simulate(profile: &Profile, tick: Duration, duration: Duration) -> u64 {
+    let clock = VirtualClock::new();
+    let mut scheduler = Scheduler::new(clock.clone(), profile.clone(), tick);
+    while clock.elapsed() < duration {
+        clock.advance(scheduler.tick_interval().min(duration - clock.elapsed()));
+        let due = scheduler.next_tick();
+        scheduler.record_sent(due);
+    }
+    scheduler.sent()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Curve;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn profile(curve: &str) -> Profile {
+        Profile::new(curve.parse().unwrap(), None)
+    }
+
+    #[test]
+    fn fractional_rates_release_exact_counts() {
+        assert_eq!(simulate(&profile("constant:0.5"), TICK, Duration::from_secs(60)), 30);
+        assert_eq!(simulate(&profile("constant:2.5"), TICK, Duration::from_secs(10)), 25);
+        // A tick that does not divide the run, with the last one cut short
+        assert_eq!(simulate(&profile("constant:0.7"), Duration::from_millis(30), Duration::from_secs(100)), 70);
+        assert_eq!(simulate(&Profile::new(Curve::Constant(1000.0), Some(0.25)), TICK, Duration::from_secs(40)), 10);
+    }
+
+    #[test]
+    fn a_stall_does_not_burst() {
+        let clock = VirtualClock::new();
+        let mut scheduler = Scheduler::new(clock.clone(), profile("constant:10"), TICK);
+        clock.advance(TICK);
+        assert_eq!(scheduler.next_tick(), 1);
+        clock.advance(Duration::from_secs(10));
+        // 10 TPS over the capped MAX_CATCH_UP_TICKS ticks of 100ms, not over the 10s stall
+        assert_eq!(scheduler.next_tick(), MAX_CATCH_UP_TICKS as u64);
+        for _ in 0..10 {
+            clock.advance(TICK);
+            assert_eq!(scheduler.next_tick(), 1);
+        }
+    }
+
+    #[test]
+    fn ramp_and_step_totals_follow_the_curve() {
+        // 50 over the ramp, then 10 TPS held for 10s
+        assert_eq!(simulate(&profile("ramp:0,10,10"), TICK, Duration::from_secs(20)), 150);
+        // 5 TPS for 10s, then 20 TPS held for 10s; the tick ending at 10s reads the second step
+        let sent = simulate(&profile("steps:5@10,20@5"), TICK, Duration::from_secs(20));
+        assert!(sent.abs_diff(250) <= 2, "sent {}", sent);
+    }
+}
@@ -0,0 +1,247 @@
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::{
+    distribution,
+    tx::{self, MAXIMUM_STANDARD_TRANSACTION_MASS, MAX_INPUTS_PER_TRANSACTION},
+};
+
+// ----------------------- shapes -----------------------
+/// Inputs and outputs of a spam transaction, parsed from one `TX_SHAPES` entry:
+///
+/// - `NxM`: N inputs and M outputs
+/// - `max-inputs`: as many inputs as fit under the standard mass limit, 1 output
+/// - `max-mass`: 1-in 1-out, padded with payload up to the standard mass limit
+/// - `mass:N`: 1-in 1-out, padded with payload up to mass N
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shape {
+    pub inputs: usize,
+    pub outputs: usize,
+    /// Compute mass the payload is padded up to
+    pub target_mass: Option<u64>,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape { inputs: 1, outputs: 1, target_mass: None }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let shape = match s.as_str() {
+            "max-inputs" => {
+                let inputs = (1..=MAX_INPUTS_PER_TRANSACTION)
+                    .take_while(|&inputs| tx::compute_mass(inputs, 1, 0) <= MAXIMUM_STANDARD_TRANSACTION_MASS)
+                    .last()
+                    .unwrap_or(1);
+                Shape { inputs, outputs: 1, target_mass: None }
+            }
+            "max-mass" => Shape { target_mass: Some(MAXIMUM_STANDARD_TRANSACTION_MASS), ..Shape::default() },
+            _ => match s.strip_prefix("mass:") {
+                Some(mass) => {
+                    let mass = mass.parse::<u64>().map_err(|e| format!("invalid mass {}: {}", mass, e))?;
+                    Shape { target_mass: Some(mass), ..Shape::default() }
+                }
+                None => {
+                    let (inputs, outputs) = s
+                        .split_once('x')
+                        .ok_or_else(|| format!("invalid shape {}. Expected NxM, max-inputs, max-mass or mass:N", s))?;
+                    let inputs =
+                        inputs.parse::<usize>().map_err(|e| format!("invalid input count {}: {}", inputs, e))?;
+                    let outputs =
+                        outputs.parse::<usize>().map_err(|e| format!("invalid output count {}: {}", outputs, e))?;
+                    Shape { inputs, outputs, target_mass: None }
+                }
+            },
+        };
+        shape.validate()?;
+        Ok(shape)
+    }
+}
+
+impl std::fmt::Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.target_mass {
+            Some(mass) => write!(f, "mass:{}", mass),
+            None => write!(f, "{}x{}", self.inputs, self.outputs),
+        }
+    }
+}
+
+/// Fee and outputs of a shape for a concrete set of inputs.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub output_amounts: Vec<u64>,
+    pub fee: u64,
+    pub mass: u64,
+}
+
+impl Shape {
+    fn validate(&self) -> Result<(), String> {
+        if self.inputs == 0 || self.outputs == 0 {
+            return Err(format!("shape {} needs at least one input and one output", self));
+        }
+        if self.inputs > MAX_INPUTS_PER_TRANSACTION {
+            return Err(format!("shape {} has more than {} inputs", self, MAX_INPUTS_PER_TRANSACTION));
+        }
+        let mass = self.target_mass.unwrap_or(0).max(tx::compute_mass(self.inputs, self.outputs, 0));
+        if mass > MAXIMUM_STANDARD_TRANSACTION_MASS {
+            return Err(format!("shape {} has mass {}, above the standard limit", self, mass));
+        }
+        Ok(())
+    }
+
+    /// Payload length for this shape: the padding up to the target mass, or `sampled`
+    /// capped so the compute mass stays under the standard limit.
+    pub fn payload_len(&self, sampled: usize) -> usize {
+        let base = tx::compute_mass(self.inputs, self.outputs, 0);
+        match self.target_mass {
+            Some(mass) => mass.saturating_sub(base) as usize,
+            None => sampled.min(MAXIMUM_STANDARD_TRANSACTION_MASS.saturating_sub(base) as usize),
+        }
+    }
+
+    /// Least the reserved inputs must hold together: `min_output` per output (raised
+    /// so storage mass stays under the limit when there are several) plus the fee.
+    pub fn min_input_total(&self, fee_rate: f64, payload_len: usize, min_output: u64) -> u64 {
+        let min_output = match self.outputs {
+            1 => min_output,
+            outputs => min_output.max(tx::min_output_amount(outputs)),
+        };
+        let fee = tx::fee_at_rate(fee_rate, tx::compute_mass(self.inputs, self.outputs, payload_len));
+        min_output * self.outputs as u64 + fee
+    }
+
+    /// Splits `input_amounts` into equal outputs after the fee, charging the larger of
+    /// compute and storage mass. `None` if the inputs cannot fund the shape within the
+    /// standard mass limit.
+    pub fn plan(&self, input_amounts: &[u64], payload_len: usize, fee_rate: f64) -> Option<Plan> {
+        let total: u64 = input_amounts.iter().sum();
+        let mut fee = tx::fee_at_rate(fee_rate, tx::compute_mass(input_amounts.len(), self.outputs, payload_len));
+        // Smaller outputs raise storage mass, so settle the fee in a few rounds
+        for _ in 0..3 {
+            let output_amounts = split_evenly(total.checked_sub(fee)?, self.outputs);
+            let mass = tx::estimated_mass(input_amounts, &output_amounts, payload_len);
+            if mass > MAXIMUM_STANDARD_TRANSACTION_MASS {
+                return None;
+            }
+            let required = tx::fee_at_rate(fee_rate, mass);
+            if required <= fee {
+                return Some(Plan { output_amounts, fee, mass });
+            }
+            fee = required;
+        }
+        None
+    }
+}
+
+fn split_evenly(amount: u64, parts: usize) -> Vec<u64> {
+    let base = amount / parts as u64;
+    let mut amounts = vec![base; parts];
+    amounts[0] += amount - base * parts as u64;
+    amounts
+}
+
+// ----------------------- mixes -----------------------
+/// Weighted mix of shapes (`TX_SHAPES`), e.g. `1x1=70,2x2=20,max-mass=10`. An
+/// entry without a weight counts as weight 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeMix(Vec<(Shape, f64)>);
+
+impl Default for ShapeMix {
+    fn default() -> Self {
+        ShapeMix(vec![(Shape::default(), 1.0)])
+    }
+}
+
+impl FromStr for ShapeMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s
+            .split(',')
+            .map(|entry| {
+                let (shape, weight) = entry.split_once('=').unwrap_or((entry, "1"));
+                let weight = weight.trim().parse::<f64>().map_err(|e| format!("invalid weight {}: {}", weight, e))?;
+                Ok((shape.parse::<Shape>()?, weight))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(ShapeMix(distribution::check_weights(entries)?))
+    }
+}
+
+impl std::fmt::Display for ShapeMix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<String> = self.0.iter().map(|(shape, weight)| format!("{}={}", shape, weight)).collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl ShapeMix {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Shape {
+        distribution::choose_weighted(&self.0, rng).copied().unwrap_or_default()
+    }
+
+    /// True for the default mix of 1-in 1-out transactions only.
+    pub fn is_default(&self) -> bool {
+        *self == ShapeMix::default()
+    }
+
+    /// Most inputs any shape in the mix spends.
+    pub fn max_inputs(&self) -> usize {
+        self.0.iter().map(|(shape, _)| shape.inputs).max().unwrap_or(1)
+    }
+
+    /// Weighted mean of `value` over the mix, for planning.
+    pub fn mean_by(&self, value: impl Fn(&Shape) -> f64) -> f64 {
+        let total: f64 = self.0.iter().map(|(_, weight)| weight).sum();
+        self.0.iter().map(|(shape, weight)| value(shape) * weight).sum::<f64>() / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 KAS, enough for any shape to stay clear of storage mass.
+    const LARGE_INPUT: u64 = 10_000_000_000;
+
+    #[test]
+    fn payload_len_fills_up_to_the_mass_limit() {
+        let max_mass: Shape = "max-mass".parse().unwrap();
+        let padding = max_mass.payload_len(0);
+        assert_eq!(tx::compute_mass(1, 1, padding), MAXIMUM_STANDARD_TRANSACTION_MASS);
+
+        let shape: Shape = "2x3".parse().unwrap();
+        assert_eq!(shape.payload_len(10), 10);
+        let capped = shape.payload_len(usize::MAX);
+        assert_eq!(tx::compute_mass(2, 3, capped), MAXIMUM_STANDARD_TRANSACTION_MASS);
+        assert!("mass:100001".parse::<Shape>().is_err());
+    }
+
+    #[test]
+    fn plan_charges_the_fee_up_to_the_mass_limit() {
+        let shape: Shape = "max-mass".parse().unwrap();
+        let payload_len = shape.payload_len(0);
+        let plan = shape.plan(&[LARGE_INPUT], payload_len, 1.0).unwrap();
+        assert_eq!(plan.mass, MAXIMUM_STANDARD_TRANSACTION_MASS);
+        assert_eq!(plan.fee, MAXIMUM_STANDARD_TRANSACTION_MASS);
+        assert_eq!(plan.output_amounts.iter().sum::<u64>() + plan.fee, LARGE_INPUT);
+        assert!(shape.plan(&[LARGE_INPUT], payload_len + 1, 1.0).is_none());
+    }
+
+    #[test]
+    fn plan_keeps_storage_mass_under_the_limit() {
+        let shape: Shape = "1x10".parse().unwrap();
+        let funded = shape.min_input_total(1.0, 0, 0);
+        let plan = shape.plan(&[funded], 0, 1.0).unwrap();
+        assert!(plan.mass <= MAXIMUM_STANDARD_TRANSACTION_MASS);
+        assert_eq!(plan.output_amounts.len(), 10);
+        assert!(shape.plan(&[funded / 2], 0, 1.0).is_none());
+    }
+}
@@ -0,0 +1,1081 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_channel::Receiver;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{ScriptPublicKey, TransactionOutpoint, TransactionOutput, UtxoEntry};
+use kaspa_notify::scope::{Scope, UtxosChangedScope, VirtualChainChangedScope, VirtualDaaScoreChangedScope};
+use kaspa_rpc_core::{Notification, UtxosChangedNotification};
+use kaspa_txscript::pay_to_address_script;
+use log::{debug, info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use secp256k1::Keypair;
+use tokio::{
+    sync::Notify,
+    task::JoinHandle,
+    time::{Instant, MissedTickBehavior},
+};
+
+use crate::{
+    acceptance::AcceptanceTracker,
+    backend::RpcBackend,
+    budget::{FeeBudget, Grant, Refusal},
+    client_pool::{self, ClientPool},
+    config::Config,
+    fees::{self, FeeEstimator},
+    keys, preflight,
+    profile::Profile,
+    rejection::{self, Backoff, Rejection, RejectionCounts},
+    replenish::{ReplenishCounts, Replenisher},
+    rpc,
+    scheduler::{Scheduler, SystemClock},
+    shape::{Plan, Shape},
+    tx::{self, format_kas},
+    tx_log::{self, TxLog, TxRecord},
+    utxo::{Drift, PoolStats, Utxo, UtxoPool},
+    wallets::{self, Wallet},
+};
+
+/// Seconds covered by the rolling TPS average.
+const ROLLING_WINDOW_SECS: usize = 10;
+/// How long to wait for in-flight submits when the run or a trial ends.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most submit latency samples kept until they are taken.
+const MAX_LATENCY_SAMPLES: usize = 100_000;
+/// Resubmits of one spam transaction after an orphan or fee-too-low rejection.
+const MAX_RESUBMITS: usize = 3;
+/// Factor applied to the fee rate of a transaction rejected for its fee.
+const FEE_BUMP_FACTOR: f64 = 2.0;
+/// Longest wait for the parents of an orphan before it is resubmitted.
+const ORPHAN_PARENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest gap between node notifications before the subscription is presumed lost.
+/// VirtualDaaScoreChanged alone arrives with every block.
+const NOTIFICATION_SILENCE: Duration = Duration::from_secs(10);
+
+pub struct Stats {
+    submitted: AtomicU64,
+    failed: AtomicU64,
+    inflight: AtomicU64,
+    /// Submit round trips since the last `take_latencies`
+    latencies: Mutex<Vec<Duration>>,
+    /// Submit-to-accept latency of the submitted transactions
+    pub acceptance: AcceptanceTracker,
+    pub rejections: RejectionCounts,
+    pub replenished: ReplenishCounts,
+}
+
+impl Stats {
+    fn new(acceptance_timeout: Duration) -> Self {
+        Stats {
+            submitted: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            inflight: AtomicU64::new(0),
+            latencies: Mutex::new(Vec::new()),
+            acceptance: AcceptanceTracker::new(acceptance_timeout),
+            rejections: RejectionCounts::default(),
+            replenished: ReplenishCounts::default(),
+        }
+    }
+
+    pub fn submitted(&self) -> u64 {
+        self.submitted.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Waits up to `DRAIN_TIMEOUT` for in-flight submits to finish. Returns how many
+    /// are still in flight.
+    pub async fn drain(&self) -> u64 {
+        let started = Instant::now();
+        while self.inflight.load(Ordering::Relaxed) > 0 && started.elapsed() < DRAIN_TIMEOUT {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    pub fn take_latencies(&self) -> Vec<Duration> {
+        std::mem::take(&mut self.latencies.lock().unwrap())
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() < MAX_LATENCY_SAMPLES {
+            latencies.push(latency);
+        }
+    }
+}
+
+/// The `pct` percentile of `samples`, or zero without samples.
+pub fn percentile(samples: &mut [Duration], pct: f64) -> Duration {
+    if samples.is_empty() {
+        return Duration::ZERO;
+    }
+    samples.sort_unstable();
+    let index = ((pct / 100.0) * (samples.len() - 1) as f64).round() as usize;
+    samples[index]
+}
+
+/// Default mode: splits UTXOs up to `TARGET_UTXO_COUNT`, then sends self-payments
+/// shaped by `TX_SHAPES` (1-in 1-out by default) at `TARGET_TPS` for
+/// `SPAM_DURATION_SECONDS`. With `WALLETS_FILE` the master key funds the wallets
+/// instead and they pay each other.
+pub async fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let session = prepare(config, "🚀 Kaspa transaction generator").await?;
+    split(config, &session).await?;
+    let profile = config.traffic_profile();
+    if profile.is_capped() {
+        warn!("UNLEASHED=false: capping {} at {} TPS", profile.curve(), profile.peak_tps());
+    }
+    let mut sender = Sender::start(config, &session);
+    let reporter = tokio::spawn(report_loop(session.clone(), sender.stats().clone(), profile.clone()));
+
+    match &config.fee_rate_distribution {
+        Some(distribution) => println!("📤 Sending: {} (fee rate {:?})", profile, distribution),
+        None => println!(
+            "📤 Sending: {} (fee {} per tx, {} tier)",
+            profile,
+            format_kas(tx::required_fee_base(session.fees.rate())),
+            session.fees.tier()
+        ),
+    }
+    if let Some(distribution) = &config.payload_size_distribution {
+        println!("   Payload size: {:?} bytes", distribution);
+    }
+    if !config.tx_shapes.is_default() {
+        println!("   Shapes: {}", config.tx_shapes);
+    }
+    if config.chain_depth > 0 {
+        println!("   Chaining: spends in-flight outputs, up to {} unconfirmed ancestors", config.chain_depth);
+    }
+    let mut scheduler = Scheduler::new(SystemClock::new(), profile, Duration::from_millis(config.millis_per_tick));
+    let started = Instant::now();
+    let duration = (config.spam_duration_seconds > 0).then(|| Duration::from_secs(config.spam_duration_seconds));
+    sender.send(config, &mut scheduler, duration).await;
+    reporter.abort();
+    let stats = sender.finish().await;
+
+    println!();
+    println!("🏁 Done after {}s", started.elapsed().as_secs());
+    println!("   Submitted:    {}", stats.submitted());
+    println!("   Failed:       {}", stats.failed());
+    if stats.rejections.total() > 0 {
+        println!("   Rejections:   {}", stats.rejections.describe());
+    }
+    if stats.replenished.splits() > 0 {
+        println!("   Replenished:  {} UTXOs in {} splits", stats.replenished.outputs(), stats.replenished.splits());
+    }
+    let acceptance = stats.acceptance.summary();
+    if stats.acceptance.is_active() {
+        println!("   Accepted:     {} ({})", acceptance.accepted, acceptance.describe_latency());
+        println!("   Not accepted: {}", acceptance.never_accepted);
+        if config.chain_depth > 0 {
+            for (depth, at_depth) in stats.acceptance.summary_by_depth().iter().enumerate() {
+                println!(
+                    "   Chain depth {}: {} accepted ({}), {} not accepted",
+                    depth,
+                    at_depth.accepted,
+                    at_depth.describe_latency(),
+                    at_depth.never_accepted
+                );
+            }
+        }
+    } else {
+        println!("   Accepted:     unknown, no VirtualChainChanged subscription");
+    }
+    println!(
+        "   Achieved TPS: {:.1} (target {:.1})",
+        scheduler.achieved_tps(),
+        scheduler.profile().mean_tps(started.elapsed())
+    );
+    println!("   Fees paid:    {}", session.budget.describe());
+    Ok(())
+}
+
+/// Connections, UTXO pool, fee state and transaction log of a sending run.
+#[derive(Clone)]
+pub struct Session {
+    pub clients: Arc<ClientPool>,
+    pub keypair: Keypair,
+    pub address: Address,
+    pub pool: Arc<UtxoPool>,
+    /// Wallets of `WALLETS_FILE`, funded by the master key; empty without one
+    pub wallets: Vec<Wallet>,
+    pub budget: Arc<FeeBudget>,
+    pub fees: Arc<FeeEstimator>,
+    pub tx_log: Option<Arc<TxLog>>,
+}
+
+impl Session {
+    /// The master key as a wallet.
+    pub fn master(&self) -> Wallet {
+        Wallet {
+            keypair: self.keypair,
+            address: self.address.clone(),
+            script_public_key: pay_to_address_script(&self.address),
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// The wallets spam is sent from: those of `WALLETS_FILE`, or the master key.
+    pub fn senders(&self) -> Vec<Wallet> {
+        if self.wallets.is_empty() {
+            vec![self.master()]
+        } else {
+            self.wallets.clone()
+        }
+    }
+
+    /// Pool counts summed over the senders.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.senders().iter().map(|wallet| wallet.pool.stats()).sum()
+    }
+
+    /// Confirmation lags recorded by the senders' pools since the last call.
+    pub fn take_confirmation_lags(&self) -> Vec<Duration> {
+        self.senders().iter().flat_map(|wallet| wallet.pool.take_confirmation_lags()).collect()
+    }
+}
+
+/// Connects, runs the preflight checks and loads the pool and fee estimate.
+/// Shared by the spam run, `--find-max-tps` and scenarios.
+pub async fn prepare(config: &Config, title: &str) -> Result<Session, Box<dyn Error>> {
+    config.require_mainnet_confirmation()?;
+    let keypair = keys::keypair_from_hex(config.private_key_hex()?)?;
+    let address = keys::address_for(&keypair, config.network);
+    println!("{} ({})", title, config.network);
+    println!("   Address: {}", String::from(&address));
+    println!("   Node:    {} ({} connections)", config.rpc_url, config.client_pool_size.max(1));
+
+    let clients = ClientPool::connect(&config.rpc_url, config.wrpc_encoding, config.client_pool_size, config.client_selection).await?;
+    let network = config.network;
+    clients.call(|client| async move { preflight::check(&*client, network).await }).await?;
+    let pool = Arc::new(UtxoPool::new(config.maturity(), Duration::from_secs(config.max_pending_age_secs)));
+    refresh(&clients, &address, &pool).await?;
+    let wallets = match &config.wallets_file {
+        Some(path) => {
+            let wallets: Vec<Wallet> = wallets::load_keys(path)?
+                .into_iter()
+                .map(|keypair| {
+                    let pool =
+                        UtxoPool::new(config.maturity(), Duration::from_secs(config.max_pending_age_secs));
+                    Wallet::new(keypair, config.network, Arc::new(pool))
+                })
+                .collect();
+            refresh_wallets(&clients, &wallets).await?;
+            println!("   Wallets: {} from {}", wallets.len(), path);
+            wallets
+        }
+        None => Vec::new(),
+    };
+    let budget = Arc::new(FeeBudget::new(config.max_total_fees_sompi, config.max_fees_per_minute_sompi));
+    let fees = Arc::new(FeeEstimator::new(config.fee_tier, config.base_fee_rate));
+    let estimator = &fees;
+    if let Err(e) = clients.call(|client| async move { estimator.refresh(&*client).await }).await {
+        warn!("No fee estimate from the node, paying BASE_FEE_RATE: {}", e);
+    }
+    let tx_log = match &config.tx_log_file {
+        Some(path) => Some(Arc::new(TxLog::create(path)?)),
+        None => None,
+    };
+    Ok(Session { clients, keypair, address, pool, wallets, budget, fees, tx_log })
+}
+
+/// Runs the split phase for `session` up to `TARGET_UTXO_COUNT` UTXOs, or funds
+/// the wallets of `WALLETS_FILE` with them.
+pub async fn split(config: &Config, session: &Session) -> Result<(), Box<dyn Error>> {
+    if !session.wallets.is_empty() {
+        return fund_wallets(config, session).await;
+    }
+    let available = session.pool.stats().available;
+    if available >= config.target_utxo_count {
+        println!("✅ {} spendable UTXOs, skipping split phase", available);
+        return Ok(());
+    }
+    let missing = config.target_utxo_count - available;
+    println!("✂️  Splitting: {} UTXOs missing", missing);
+    let master = session.master();
+    split_phase(config, session, vec![&master; missing]).await
+}
+
+pub async fn refresh(clients: &Arc<ClientPool>, address: &Address, pool: &UtxoPool) -> Result<(), Box<dyn Error>> {
+    clients.call(|client| async move { rpc::refresh_pool(&*client, address, pool).await }).await
+}
+
+pub async fn refresh_wallets(clients: &Arc<ClientPool>, wallets: &[Wallet]) -> Result<Drift, Box<dyn Error>> {
+    clients.call(|client| async move { rpc::refresh_wallets(&*client, wallets).await }).await
+}
+
+/// Refreshes `wallets`, then prunes their stale reservations and releases the
+/// inputs of submissions the node dropped. Returns the drift and how many entries
+/// were pruned or released.
+async fn refresh_and_prune(clients: &Arc<ClientPool>, wallets: &[Wallet]) -> Result<(Drift, usize), Box<dyn Error>> {
+    let drift = refresh_wallets(clients, wallets).await?;
+    let pruned: usize = wallets.iter().map(|wallet| wallet.pool.prune_stale()).sum();
+    let mut released = 0;
+    for wallet in wallets {
+        let pool = &wallet.pool;
+        released += clients.call(|client| async move { rpc::release_abandoned(&*client, pool).await }).await?;
+    }
+    Ok((drift, pruned + released))
+}
+
+// ----------------------- split phase -----------------------
+/// Splits the largest master UTXO into outputs of `AMOUNT_PER_UTXO`, one per entry
+/// of `recipients` and at most `OUTPUTS_PER_TRANSACTION` per transaction, until
+/// every recipient is paid or funds run out.
+async fn split_phase(config: &Config, session: &Session, recipients: Vec<&Wallet>) -> Result<(), Box<dyn Error>> {
+    let Session { clients, keypair, pool, budget, fees, .. } = session;
+    let master = session.master();
+    let mut paid_outputs = 0;
+    while paid_outputs < recipients.len() {
+        let missing = recipients.len() - paid_outputs;
+        let Some(inputs) = pool.reserve(1, 0) else {
+            warn!("No spendable UTXO left to split");
+            break;
+        };
+        let input_amount = inputs[0].1.amount;
+        let num_outputs = config.outputs_per_transaction.max(1).min(missing);
+        let fee = tx::required_fee_splitting(fees.rate(), input_amount, config.amount_per_utxo, num_outputs);
+        let spent = config.amount_per_utxo * num_outputs as u64 + fee;
+        let outpoints = [inputs[0].0];
+        if spent > input_amount {
+            warn!("Largest UTXO ({}) cannot fund another split; {} UTXOs still missing", format_kas(input_amount), missing);
+            pool.release(&outpoints);
+            break;
+        }
+        let mass = tx::splitting_mass(input_amount, config.amount_per_utxo, num_outputs);
+        if mass > tx::MAXIMUM_STANDARD_TRANSACTION_MASS {
+            pool.release(&outpoints);
+            return Err(format!(
+                "Split transaction mass {} exceeds the standard limit; reduce OUTPUTS_PER_TRANSACTION or raise AMOUNT_PER_UTXO",
+                mass
+            )
+            .into());
+        }
+
+        let mut owners = recipients[paid_outputs..paid_outputs + num_outputs].to_vec();
+        let mut outputs: Vec<TransactionOutput> = owners
+            .iter()
+            .map(|owner| TransactionOutput::new(config.amount_per_utxo, owner.script_public_key.clone()))
+            .collect();
+        // Change below MIN_CHANGE_SOMPI is left to the miner rather than creating dust
+        let change = input_amount - spent;
+        if change >= config.min_change_sompi {
+            outputs.push(TransactionOutput::new(change, master.script_public_key.clone()));
+            owners.push(&master);
+        }
+        let paid = input_amount - outputs.iter().map(|output| output.value).sum::<u64>();
+        let Ok(grant) = budget.spend(paid).await else {
+            warn!("Fee budget exhausted during the split phase; {} UTXOs still missing", missing);
+            pool.release(&outpoints);
+            break;
+        };
+        let tx = tx::build_signed_tx(*keypair, &inputs, outputs, vec![]);
+        let signed = &tx;
+        let txid = match clients.call(|client| async move { rpc::submit_transaction(&*client, signed).await }).await {
+            Ok(txid) => txid,
+            Err(e) if Rejection::classify(&e.to_string()) == Rejection::AlreadyInMempool => tx.id(),
+            Err(e) if client_pool::is_unreachable(&e.to_string()) => {
+                pool.mark_submitted(tx.id(), &outpoints, Vec::new());
+                return Err(format!("Split transaction {} may not have reached the node: {}", tx.id(), e).into());
+            }
+            Err(e) => {
+                budget.refund(grant);
+                pool.release(&outpoints);
+                return Err(format!("Split transaction {} was rejected: {}", tx.id(), e).into());
+            }
+        };
+        pool.mark_submitted(txid, &outpoints, Vec::new());
+        for (index, (output, owner)) in tx.outputs.iter().zip(owners).enumerate() {
+            let entry = UtxoEntry::new(output.value, output.script_public_key.clone(), 0, false);
+            owner.pool.mark_submitted(txid, &[], vec![(TransactionOutpoint::new(txid, index as u32), entry)]);
+        }
+        paid_outputs += num_outputs;
+        info!(
+            "Split {} into {} outputs, fee {} ({} missing)",
+            txid,
+            num_outputs,
+            format_kas(paid),
+            recipients.len() - paid_outputs
+        );
+
+        wait_for_pending(config, clients, std::slice::from_ref(&master)).await?;
+    }
+    Ok(())
+}
+
+/// Pays every wallet of `WALLETS_FILE` from the master key until it holds its share
+/// of `TARGET_UTXO_COUNT` spendable UTXOs, then waits until they are spendable.
+async fn fund_wallets(config: &Config, session: &Session) -> Result<(), Box<dyn Error>> {
+    let wallets = &session.wallets;
+    let share = config.target_utxo_count.div_ceil(wallets.len()).max(1);
+    let missing: Vec<usize> =
+        wallets.iter().map(|wallet| share.saturating_sub(wallet.pool.stats().available)).collect();
+    let total: usize = missing.iter().sum();
+    if total == 0 {
+        println!("✅ {} wallets hold {} spendable UTXOs each, skipping funding", wallets.len(), share);
+        return Ok(());
+    }
+    println!("✂️  Funding {} wallets with {} UTXOs each: {} UTXOs missing", wallets.len(), share, total);
+    // Interleave the wallets so every split transaction pays several of them
+    let rounds = missing.iter().copied().max().unwrap_or(0);
+    let recipients: Vec<&Wallet> = (0..rounds)
+        .flat_map(|round| wallets.iter().zip(&missing).filter(move |(_, missing)| round < **missing))
+        .map(|(wallet, _)| wallet)
+        .collect();
+    split_phase(config, session, recipients).await?;
+    wait_for_pending(config, &session.clients, wallets).await
+}
+
+/// Waits until every output we created for `wallets` is spendable.
+async fn wait_for_pending(
+    config: &Config,
+    clients: &Arc<ClientPool>,
+    wallets: &[Wallet],
+) -> Result<(), Box<dyn Error>> {
+    while wallets.iter().any(|wallet| wallet.pool.stats().pending_change > 0) {
+        tokio::time::sleep(Duration::from_secs(config.utxo_refresh_secs.max(1))).await;
+        let (_, pruned) = refresh_and_prune(clients, wallets).await?;
+        if pruned > 0 {
+            warn!("Gave up on {} split outputs older than {}s", pruned, config.max_pending_age_secs);
+        }
+    }
+    Ok(())
+}
+
+// ----------------------- sender -----------------------
+/// Why `Sender::send` returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendEnd {
+    Deadline,
+    BudgetExhausted,
+    /// A stop condition passed to `send_until` fired
+    Stopped(String),
+}
+
+/// Paces transactions out of the senders' pools and keeps the pools, DAA score and
+/// fee rate fresh in the background until `finish`. With several senders the rate
+/// is spread evenly over them and every transaction pays other wallets.
+pub struct Sender {
+    context: Arc<SendContext>,
+    fees: Arc<FeeEstimator>,
+    refresh_now: Arc<Notify>,
+    rng: StdRng,
+    tasks: Vec<JoinHandle<()>>,
+    /// Sender whose turn is next
+    next_sender: usize,
+    /// `CHAIN_DEPTH`: most unconfirmed ancestors of a transaction
+    chain_depth: u32,
+    /// Splits large outputs when a sender runs low, with `REPLENISH_LOW_WATER` set
+    replenisher: Option<Arc<Replenisher>>,
+    /// Warned that the pool cannot fund a shape
+    unfundable: bool,
+}
+
+impl Sender {
+    pub fn start(config: &Config, session: &Session) -> Self {
+        if config.tx_shapes.max_inputs() > config.target_utxo_count {
+            warn!(
+                "TX_SHAPES spends up to {} inputs per transaction but TARGET_UTXO_COUNT is {}",
+                config.tx_shapes.max_inputs(),
+                config.target_utxo_count
+            );
+        }
+        let senders = session.senders();
+        let refresh_now = Arc::new(Notify::new());
+        let context = Arc::new(SendContext {
+            clients: session.clients.clone(),
+            senders: senders.clone(),
+            stats: Arc::new(Stats::new(Duration::from_secs(config.acceptance_timeout_secs))),
+            budget: session.budget.clone(),
+            tx_log: session.tx_log.clone(),
+            refresh_now: refresh_now.clone(),
+            backoff: Backoff::default(),
+            utxo_events: Arc::new(AtomicBool::new(false)),
+        });
+        let clients = &session.clients;
+        let mut tasks = vec![
+            tokio::spawn(refresh_loop(
+                clients.clone(),
+                senders.clone(),
+                refresh_now.clone(),
+                context.utxo_events.clone(),
+                Duration::from_secs(config.utxo_refresh_secs.max(1)),
+                Duration::from_secs(config.utxo_reconcile_secs.max(1)),
+                config.max_pending_age_secs,
+            )),
+            tokio::spawn(notification_loop(
+                clients.clone(),
+                senders,
+                context.stats.clone(),
+                context.utxo_events.clone(),
+                refresh_now.clone(),
+            )),
+            tokio::spawn(fees::follow(
+                clients.clone(),
+                session.fees.clone(),
+                Duration::from_secs(config.fee_refresh_secs.max(1)),
+            )),
+        ];
+        let replenisher = (config.replenish_low_water > 0).then(|| {
+            let (replenisher, handoffs) = Replenisher::new(
+                config,
+                clients.clone(),
+                context.senders.clone(),
+                session.budget.clone(),
+                session.fees.clone(),
+            );
+            tasks.push(tokio::spawn(replenisher.clone().run(handoffs, context.stats.clone())));
+            replenisher
+        });
+        Sender {
+            context,
+            fees: session.fees.clone(),
+            refresh_now,
+            rng: StdRng::from_entropy(),
+            tasks,
+            next_sender: 0,
+            chain_depth: config.chain_depth,
+            replenisher,
+            unfundable: false,
+        }
+    }
+
+    pub fn stats(&self) -> &Arc<Stats> {
+        &self.context.stats
+    }
+
+    /// Sends at the pace of `scheduler` for `duration` (forever if `None`) or until
+    /// the fee budget is exhausted.
+    pub async fn send(
+        &mut self,
+        config: &Config,
+        scheduler: &mut Scheduler<SystemClock>,
+        duration: Option<Duration>,
+    ) -> SendEnd {
+        self.send_until(config, scheduler, duration, || None).await
+    }
+
+    /// Like `send`, but also stops as soon as `stop` returns a reason; it is
+    /// checked every tick.
+    pub async fn send_until(
+        &mut self,
+        config: &Config,
+        scheduler: &mut Scheduler<SystemClock>,
+        duration: Option<Duration>,
+        mut stop: impl FnMut() -> Option<String>,
+    ) -> SendEnd {
+        let budget = self.context.budget.clone();
+        let mut ticker = tokio::time::interval(scheduler.tick_interval());
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let deadline = duration.map(|duration| Instant::now() + duration);
+        let mut throttled = false;
+        loop {
+            ticker.tick().await;
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return SendEnd::Deadline;
+            }
+            if let Some(reason) = stop() {
+                return SendEnd::Stopped(reason);
+            }
+            let due = scheduler.next_tick();
+            if self.context.backoff.is_active() {
+                scheduler.record_sent(0);
+                continue;
+            }
+            let mut sent = 0;
+            let mut refusal = None;
+            while sent < due {
+                let spec = draw(config, &self.fees, &mut self.rng);
+                let min_total = spec.shape.min_input_total(spec.fee_rate, spec.payload_len, config.min_change_sompi);
+                let Some((from, inputs, chain_depth)) = self.reserve(spec.shape.inputs, min_total) else {
+                    // UtxosChanged notifications refill the pools without polling
+                    if !self.context.utxo_events.load(Ordering::Relaxed) {
+                        self.refresh_now.notify_one();
+                    }
+                    break;
+                };
+                // Outputs of confirmed transactions only, so splits never extend a chain
+                let replenisher = self.replenisher.as_ref().filter(|_| chain_depth == 0);
+                if replenisher.is_some_and(|replenisher| replenisher.offer(from, &inputs)) {
+                    continue;
+                }
+                let pool = &self.context.senders[from].pool;
+                let outpoints: Vec<TransactionOutpoint> = inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+                let input_amounts: Vec<u64> = inputs.iter().map(|(_, entry)| entry.amount).collect();
+                let Some(plan) = spec.shape.plan(&input_amounts, spec.payload_len, spec.fee_rate) else {
+                    pool.release(&outpoints);
+                    if !self.unfundable {
+                        warn!("Spendable UTXOs are too small for {} transactions within the mass limit", spec.shape);
+                        self.unfundable = true;
+                    }
+                    break;
+                };
+                let grant = match budget.try_spend(plan.fee) {
+                    Ok(grant) => grant,
+                    Err(refused) => {
+                        pool.release(&outpoints);
+                        refusal = Some(refused);
+                        break;
+                    }
+                };
+                let to = (0..plan.output_amounts.len()).map(|_| self.recipient(from)).collect();
+                spawn_submit(self.context.clone(), from, inputs, chain_depth, to, spec, plan, grant);
+                sent += 1;
+            }
+            scheduler.record_sent(sent);
+            match refusal {
+                Some(Refusal::Exhausted) => {
+                    println!("💸 Fee budget of {} reached, stopping", format_kas(config.max_total_fees_sompi));
+                    return SendEnd::BudgetExhausted;
+                }
+                Some(Refusal::PerMinute) if !throttled => {
+                    warn!(
+                        "Per-minute fee ceiling of {} reached, pausing sends",
+                        format_kas(config.max_fees_per_minute_sompi)
+                    );
+                    throttled = true;
+                }
+                Some(Refusal::PerMinute) => {}
+                None => throttled = false,
+            }
+        }
+    }
+
+    /// Reserves inputs from the next sender in turn that can fund them, along with
+    /// the chain depth of the transaction spending them. With `CHAIN_DEPTH` set,
+    /// outputs of in-flight transactions are spent before confirmed ones.
+    fn reserve(&mut self, num_inputs: usize, min_total: u64) -> Option<(usize, Vec<Utxo>, u32)> {
+        let senders = &self.context.senders;
+        for _ in 0..senders.len() {
+            let from = self.next_sender;
+            self.next_sender = (self.next_sender + 1) % senders.len();
+            let pool = &senders[from].pool;
+            if self.chain_depth > 0 {
+                if let Some((inputs, chain_depth)) = pool.reserve_chained(num_inputs, min_total, self.chain_depth) {
+                    return Some((from, inputs, chain_depth));
+                }
+            }
+            if let Some(inputs) = pool.reserve(num_inputs, min_total) {
+                return Some((from, inputs, 0));
+            }
+        }
+        None
+    }
+
+    /// A random sender other than `from` to pay, or `from` itself when it sends alone.
+    fn recipient(&mut self, from: usize) -> usize {
+        match self.context.senders.len() {
+            1 => from,
+            count => (from + self.rng.gen_range(1..count)) % count,
+        }
+    }
+
+    /// Waits for in-flight submits and their acceptance, stops the background tasks
+    /// and flushes the transaction log.
+    pub async fn finish(self) -> Arc<Stats> {
+        let stats = self.context.stats.clone();
+        stats.drain().await;
+        stats.acceptance.settle().await;
+        for task in &self.tasks {
+            task.abort();
+        }
+        if let Some(tx_log) = &self.context.tx_log {
+            tx_log.flush();
+        }
+        stats
+    }
+}
+
+/// State shared by the send loop and its submit tasks.
+struct SendContext {
+    clients: Arc<ClientPool>,
+    senders: Vec<Wallet>,
+    stats: Arc<Stats>,
+    budget: Arc<FeeBudget>,
+    tx_log: Option<Arc<TxLog>>,
+    /// Wakes the refresh loop, e.g. after a double spend
+    refresh_now: Arc<Notify>,
+    /// Pause after the node reported a full mempool
+    backoff: Backoff,
+    /// Set while UtxosChanged notifications keep the pools current
+    utxo_events: Arc<AtomicBool>,
+}
+
+/// Parameters drawn for one spam transaction.
+struct TxSpec {
+    shape: Shape,
+    fee_rate: f64,
+    payload_len: usize,
+}
+
+/// Samples the shape, fee rate and payload size of the next transaction. Without
+/// `TX_SHAPES` or distributions this is a 1-in 1-out transaction at the estimator's
+/// rate with no payload. Rates are never below `BASE_FEE_RATE` and payloads never
+/// push the compute mass over the standard limit.
+fn draw(config: &Config, fees: &FeeEstimator, rng: &mut StdRng) -> TxSpec {
+    let shape = config.tx_shapes.sample(rng);
+    let fee_rate = match &config.fee_rate_distribution {
+        Some(distribution) => distribution.sample(rng).max(config.base_fee_rate as f64),
+        None => fees.rate() as f64,
+    };
+    let sampled = match &config.payload_size_distribution {
+        Some(distribution) => distribution.sample(rng).round() as usize,
+        None => 0,
+    };
+    TxSpec { shape, fee_rate, payload_len: shape.payload_len(sampled) }
+}
+
+/// Builds, signs and submits a payment from sender `from`, spending `inputs` into
+/// the planned outputs to the senders in `to`, in the background. The fee has
+/// already been granted by the budget as `grant` and is refunded on rejection.
+/// `chain_depth` counts the unconfirmed ancestors of the transaction; its outputs
+/// inherit it.
+///
+/// Rejections are classified and handled per category: orphans, e.g. chained spends
+/// whose parent has not reached the node, are resubmitted once their parents are
+/// accepted, a fee too low is bumped within the budget and
+/// resubmitted, a double spend quarantines the inputs already spent, releases the
+/// others and refreshes the pools, and a full mempool pauses the send loop. A transaction already in the mempool counts
+/// as submitted; the inputs of one no connection could deliver stay reserved.
+#[allow(clippy::too_many_arguments)]
+fn spawn_submit(
+    context: Arc<SendContext>,
+    from: usize,
+    inputs: Vec<Utxo>,
+    chain_depth: u32,
+    to: Vec<usize>,
+    spec: TxSpec,
+    plan: Plan,
+    grant: Grant,
+) {
+    context.stats.inflight.fetch_add(1, Ordering::Relaxed);
+    tokio::spawn(async move {
+        let sender = &context.senders[from];
+        let outpoints: Vec<TransactionOutpoint> = inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+        let input_amounts: Vec<u64> = inputs.iter().map(|(_, entry)| entry.amount).collect();
+        let submitted_at_ms = tx_log::unix_millis();
+        let (mut plan, mut fee_rate) = (plan, spec.fee_rate);
+        let mut grants = vec![grant];
+        let mut resubmits = 0;
+        let (tx, submitted) = loop {
+            let outputs = plan
+                .output_amounts
+                .iter()
+                .zip(&to)
+                .map(|(&amount, &to)| TransactionOutput::new(amount, context.senders[to].script_public_key.clone()))
+                .collect();
+            let tx = tx::build_signed_tx(sender.keypair, &inputs, outputs, vec![0; spec.payload_len]);
+            let submit_started = Instant::now();
+            context.stats.acceptance.track(tx.id(), submit_started, chain_depth);
+            let submitted = {
+                let tx = &tx;
+                context.clients.call(|client| async move { rpc::submit_transaction(&*client, tx).await }).await
+            }
+            .map_err(|e| e.to_string());
+            context.stats.record_latency(submit_started.elapsed());
+            let e = match submitted {
+                Ok(txid) => break (tx, Ok(txid)),
+                Err(e) => e,
+            };
+            let rejection = Rejection::classify(&e);
+            context.stats.rejections.record(rejection);
+            debug!("Transaction {} rejected ({}): {}", tx.id(), rejection, e);
+            if rejection == Rejection::AlreadyInMempool {
+                break (tx.clone(), Ok(tx.id()));
+            }
+            context.stats.acceptance.forget(&tx.id());
+            match rejection {
+                Rejection::Orphan if resubmits < MAX_RESUBMITS => {
+                    let parents: Vec<_> = outpoints.iter().map(|outpoint| outpoint.transaction_id).collect();
+                    let started = Instant::now();
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    while parents.iter().any(|parent| context.stats.acceptance.is_pending(parent))
+                        && started.elapsed() < ORPHAN_PARENT_TIMEOUT
+                    {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+                Rejection::FeeTooLow if resubmits < MAX_RESUBMITS => {
+                    let bumped = fee_rate * FEE_BUMP_FACTOR;
+                    let Some(bumped_plan) = spec.shape.plan(&input_amounts, spec.payload_len, bumped) else {
+                        break (tx, Err(e));
+                    };
+                    let Ok(grant) = context.budget.try_spend(bumped_plan.fee.saturating_sub(plan.fee)) else {
+                        break (tx, Err(e));
+                    };
+                    grants.push(grant);
+                    debug!("Bumping the fee rate of {} from {:.2} to {:.2}", tx.id(), fee_rate, bumped);
+                    (plan, fee_rate) = (bumped_plan, bumped);
+                }
+                Rejection::DoubleSpend => {
+                    // Only the inputs the node names are taken; without a name, any of them may be
+                    let conflicting = rejection::double_spent(&e, &outpoints);
+                    sender.pool.quarantine(if conflicting.is_empty() { &outpoints } else { &conflicting });
+                    context.refresh_now.notify_one();
+                    break (tx, Err(e));
+                }
+                Rejection::MempoolFull => {
+                    let delay = context.backoff.hit();
+                    debug!("Mempool full, pausing sends for {}ms", delay.as_millis());
+                    break (tx, Err(e));
+                }
+                _ => break (tx, Err(e)),
+            }
+            resubmits += 1;
+        };
+        let result = match submitted {
+            Ok(txid) => {
+                context.backoff.reset();
+                sender.pool.mark_submitted(txid, &outpoints, Vec::new());
+                for (index, (&amount, &to)) in plan.output_amounts.iter().zip(&to).enumerate() {
+                    let recipient = &context.senders[to];
+                    let entry = UtxoEntry::new(amount, recipient.script_public_key.clone(), 0, false);
+                    let output = (TransactionOutpoint::new(txid, index as u32), entry);
+                    recipient.pool.mark_submitted_chained(txid, &[], vec![output], chain_depth);
+                }
+                context.stats.submitted.fetch_add(1, Ordering::Relaxed);
+                "accepted".to_string()
+            }
+            Err(e) if client_pool::is_unreachable(&e) => {
+                // The node may hold it: keep the inputs and fee until the node shows whether it arrived
+                sender.pool.mark_submitted(tx.id(), &outpoints, Vec::new());
+                context.stats.failed.fetch_add(1, Ordering::Relaxed);
+                e
+            }
+            Err(e) => {
+                for grant in grants {
+                    context.budget.refund(grant);
+                }
+                sender.pool.release(&outpoints);
+                context.stats.failed.fetch_add(1, Ordering::Relaxed);
+                e
+            }
+        };
+        if let Some(tx_log) = &context.tx_log {
+            tx_log.record(&TxRecord {
+                txid: tx.id().to_string(),
+                submitted_at_ms,
+                inputs: inputs.len(),
+                outputs: plan.output_amounts.len(),
+                fee_rate,
+                fee: plan.fee,
+                mass: plan.mass,
+                payload_bytes: spec.payload_len,
+                chain_depth,
+                result,
+            });
+        }
+        context.stats.inflight.fetch_sub(1, Ordering::Relaxed);
+    });
+}
+
+/// Refreshes the senders' pools every `interval`, or sooner when the send loop
+/// runs dry. While UtxosChanged notifications keep the pools current, it only
+/// reconciles every `reconcile_interval` and logs the drift it corrects.
+async fn refresh_loop(
+    clients: Arc<ClientPool>,
+    senders: Vec<Wallet>,
+    refresh_now: Arc<Notify>,
+    utxo_events: Arc<AtomicBool>,
+    interval: Duration,
+    reconcile_interval: Duration,
+    max_pending_age_secs: u64,
+) {
+    loop {
+        let reconciling = utxo_events.load(Ordering::Relaxed);
+        tokio::select! {
+            _ = tokio::time::sleep(if reconciling { reconcile_interval } else { interval }) => {}
+            _ = refresh_now.notified() => {}
+        }
+        let (drift, pruned) = match refresh_and_prune(&clients, &senders).await {
+            Ok(refreshed) => refreshed,
+            Err(e) => {
+                warn!("Failed to refresh UTXOs: {}", e);
+                continue;
+            }
+        };
+        if reconciling && !drift.is_empty() {
+            warn!(
+                "UTXO reconciliation: {} outputs missing and {} unexpected compared to notifications, resynced",
+                drift.missing, drift.unexpected
+            );
+        }
+        if pruned > 0 {
+            warn!("Pruned {} reservations older than {}s", pruned, max_pending_age_secs);
+        }
+    }
+}
+
+/// Follows VirtualDaaScoreChanged notifications so outputs mature between
+/// refreshes, VirtualChainChanged notifications to time the acceptance of our
+/// transactions, and UtxosChanged notifications of the senders' addresses to apply
+/// their outputs to the pools as they change. All are subscribed on one connection,
+/// whose notifications share a channel. The channel outlives reconnects, so the
+/// connection is watched as well: when it drops, the stream ends or goes silent,
+/// polling takes over until the loop has resubscribed and reconciled the pools for
+/// the events it missed.
+async fn notification_loop(
+    clients: Arc<ClientPool>,
+    senders: Vec<Wallet>,
+    stats: Arc<Stats>,
+    utxo_events: Arc<AtomicBool>,
+    refresh_now: Arc<Notify>,
+) {
+    let by_script: HashMap<ScriptPublicKey, usize> =
+        senders.iter().enumerate().map(|(index, sender)| (sender.script_public_key.clone(), index)).collect();
+    let addresses: Vec<Address> = senders.iter().map(|sender| sender.address.clone()).collect();
+    loop {
+        let addresses = &addresses;
+        let subscription = clients
+            .call(|client| async move {
+                client.subscribe(Scope::VirtualDaaScoreChanged(VirtualDaaScoreChangedScope::default())).await?;
+                client.subscribe(Scope::VirtualChainChanged(VirtualChainChangedScope::new(true))).await?;
+                let notifications =
+                    client.subscribe(Scope::UtxosChanged(UtxosChangedScope::new(addresses.clone()))).await?;
+                Ok((client, notifications))
+            })
+            .await
+            .map_err(|e| e.to_string());
+        match subscription {
+            Ok((client, notifications)) => {
+                stats.acceptance.set_active();
+                utxo_events.store(true, Ordering::Relaxed);
+                refresh_now.notify_one();
+                let reason = follow_notifications(&*client, &notifications, |notification| match notification {
+                    Notification::VirtualDaaScoreChanged(changed) => {
+                        for sender in &senders {
+                            sender.pool.set_virtual_daa_score(changed.virtual_daa_score);
+                        }
+                    }
+                    Notification::VirtualChainChanged(changed) => stats.acceptance.record(&changed),
+                    Notification::UtxosChanged(changed) => apply_utxos_changed(&senders, &by_script, &changed),
+                    _ => {}
+                })
+                .await;
+                utxo_events.store(false, Ordering::Relaxed);
+                refresh_now.notify_one();
+                warn!("Node notifications {}, resubscribing", reason);
+            }
+            Err(e) => warn!("Failed to subscribe to node notifications: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Passes the notifications of a subscription to `handle` until the channel ends,
+/// the connection drops or the node is silent for NOTIFICATION_SILENCE, and returns
+/// why it stopped. Subscribe to VirtualDaaScoreChanged too, as the heartbeat.
+pub async fn follow_notifications(
+    client: &dyn RpcBackend,
+    notifications: &Receiver<Notification>,
+    mut handle: impl FnMut(Notification),
+) -> &'static str {
+    let mut watchdog = tokio::time::interval(Duration::from_secs(1));
+    let mut last_heard = Instant::now();
+    loop {
+        tokio::select! {
+            notification = notifications.recv() => {
+                let Ok(notification) = notification else { return "ended" };
+                last_heard = Instant::now();
+                handle(notification);
+            }
+            _ = watchdog.tick() => {
+                if !client.is_connected() {
+                    return "lost with the connection";
+                }
+                if last_heard.elapsed() > NOTIFICATION_SILENCE {
+                    return "went silent";
+                }
+            }
+        }
+    }
+}
+
+/// Applies a UtxosChanged notification to the pools of the senders whose scripts
+/// it touches; `by_script` maps scripts to sender indices.
+fn apply_utxos_changed(
+    senders: &[Wallet],
+    by_script: &HashMap<ScriptPublicKey, usize>,
+    changed: &UtxosChangedNotification,
+) {
+    let mut deltas: Vec<(Vec<Utxo>, Vec<TransactionOutpoint>)> = vec![Default::default(); senders.len()];
+    for entry in changed.added.iter() {
+        if let Some(&index) = by_script.get(&entry.utxo_entry.script_public_key) {
+            deltas[index].0.push((entry.outpoint.into(), entry.utxo_entry.clone().into()));
+        }
+    }
+    for entry in changed.removed.iter() {
+        if let Some(&index) = by_script.get(&entry.utxo_entry.script_public_key) {
+            deltas[index].1.push(entry.outpoint.into());
+        }
+    }
+    for (sender, (added, removed)) in senders.iter().zip(deltas) {
+        if !added.is_empty() || !removed.is_empty() {
+            sender.pool.apply_changes(added, &removed);
+        }
+    }
+}
+
+/// Logs per-second TPS and a rolling average against the profile's current target,
+/// with the submit latency and confirmation lag of the last second.
+async fn report_loop(session: Session, stats: Arc<Stats>, profile: Profile) {
+    let started = Instant::now();
+    let mut window: VecDeque<u64> = VecDeque::with_capacity(ROLLING_WINDOW_SECS);
+    let mut last_submitted = 0;
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let submitted = stats.submitted.load(Ordering::Relaxed);
+        let per_second = submitted - last_submitted;
+        last_submitted = submitted;
+        if window.len() == ROLLING_WINDOW_SECS {
+            window.pop_front();
+        }
+        window.push_back(per_second);
+        let rolling = window.iter().sum::<u64>() as f64 / window.len() as f64;
+        let info = session.clients.call(|client| async move { Ok(client.api().get_info().await?) }).await;
+        let mempool = match info {
+            Ok(info) => info.mempool_size.to_string(),
+            Err(_) => "?".to_string(),
+        };
+        stats.acceptance.expire();
+        let pool_stats = session.pool_stats();
+        let submit_p95 = percentile(&mut stats.take_latencies(), 95.0);
+        let mut lags = session.take_confirmation_lags();
+        let confirm_p95 = if lags.is_empty() {
+            "-".to_string()
+        } else {
+            format!("{:.1}s", percentile(&mut lags, 95.0).as_secs_f64())
+        };
+        info!(
+            "TPS (1s): {} | {}s avg: {:.1} / target {:.1} | sent: {} | accepted: {} | failed: {} | submit p95: {}ms | confirm p95: {} | mempool(node): {} | inflight: {} | local-pending: {} | UTXOs left: {} | clients: {} | runtime: {}s",
+            per_second,
+            ROLLING_WINDOW_SECS,
+            rolling,
+            profile.tps_at(started.elapsed()),
+            submitted,
+            stats.acceptance.accepted(),
+            stats.failed.load(Ordering::Relaxed),
+            submit_p95.as_millis(),
+            confirm_p95,
+            mempool,
+            stats.inflight.load(Ordering::Relaxed),
+            pool_stats.reserved,
+            pool_stats.available,
+            session.clients.connected(),
+            started.elapsed().as_secs()
+        );
+    }
+}
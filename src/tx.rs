@@ -0,0 +1,111 @@
+use kaspa_consensus_core::{
+    constants::{SOMPI_PER_KASPA, TX_VERSION},
+    sign::sign,
+    subnets::SUBNETWORK_ID_NATIVE,
+    tx::{MutableTransaction, Transaction, TransactionInput, TransactionOutput},
+};
+use secp256k1::Keypair;
+
+use crate::utxo::Utxo;
+
+// ----------------------- mass and fees -----------------------
+/// Most inputs we put in a single transaction, regardless of mass.
+pub const MAX_INPUTS_PER_TRANSACTION: usize = 84;
+/// Mempool standardness limit on transaction mass.
+pub const MAXIMUM_STANDARD_TRANSACTION_MASS: u64 = 100_000;
+
+const MASS_PER_SIG_OP: u64 = 1000;
+const MASS_PER_SCRIPT_PUB_KEY_BYTE: u64 = 10;
+/// KIP-9 storage mass parameter C.
+const STORAGE_MASS_PARAMETER: u64 = 1_000_000_000_000;
+
+/// version + input/output counts + lock time + subnetwork id + gas + payload hash + payload length
+const TX_OVERHEAD_BYTES: u64 = 2 + 8 + 8 + 8 + 20 + 8 + 32 + 8;
+/// outpoint + signature script (length prefix + Schnorr signature push) + sequence
+const INPUT_BYTES: u64 = 32 + 4 + 8 + 66 + 8;
+/// P2PK script: version + length prefix + 34 byte script
+const SCRIPT_PUB_KEY_BYTES: u64 = 2 + 34;
+const OUTPUT_BYTES: u64 = 8 + 8 + SCRIPT_PUB_KEY_BYTES;
+
+/// Compute mass of a Schnorr P2PK transaction with the given shape.
+pub fn compute_mass(num_inputs: usize, num_outputs: usize, payload_len: usize) -> u64 {
+    let size = TX_OVERHEAD_BYTES + INPUT_BYTES * num_inputs as u64 + OUTPUT_BYTES * num_outputs as u64 + payload_len as u64;
+    size + MASS_PER_SIG_OP * num_inputs as u64 + MASS_PER_SCRIPT_PUB_KEY_BYTE * SCRIPT_PUB_KEY_BYTES * num_outputs as u64
+}
+
+/// KIP-9 storage mass. Uses the relaxed harmonic formula for 1:N, N:1 and 2:2
+/// transactions and the arithmetic-mean formula otherwise.
+pub fn storage_mass(input_amounts: &[u64], output_amounts: &[u64]) -> u64 {
+    if output_amounts.contains(&0) {
+        return u64::MAX;
+    }
+    let harmonic_outs: u64 = output_amounts.iter().map(|&amount| STORAGE_MASS_PARAMETER / amount).sum();
+    let ins = input_amounts.len() as u64;
+    let outs = output_amounts.len() as u64;
+    if outs == 1 || ins == 1 || (outs == 2 && ins == 2) {
+        let harmonic_ins: u64 = input_amounts.iter().map(|&amount| STORAGE_MASS_PARAMETER / amount.max(1)).sum();
+        harmonic_outs.saturating_sub(harmonic_ins)
+    } else {
+        let total_in: u64 = input_amounts.iter().sum();
+        let mean_in = (total_in / ins.max(1)).max(1);
+        harmonic_outs.saturating_sub(ins * (STORAGE_MASS_PARAMETER / mean_in))
+    }
+}
+
+/// Transaction mass as seen by the mempool: the larger of compute and storage mass.
+pub fn estimated_mass(input_amounts: &[u64], output_amounts: &[u64], payload_len: usize) -> u64 {
+    compute_mass(input_amounts.len(), output_amounts.len(), payload_len).max(storage_mass(input_amounts, output_amounts))
+}
+
+/// Smallest equal output amount for which `num_outputs` outputs stay under the
+/// standard mass limit by storage mass alone, ignoring the credit of the inputs.
+pub fn min_output_amount(num_outputs: usize) -> u64 {
+    (STORAGE_MASS_PARAMETER * num_outputs as u64).div_ceil(MAXIMUM_STANDARD_TRANSACTION_MASS)
+}
+
+pub fn required_fee(fee_rate: u64, mass: u64) -> u64 {
+    fee_rate.saturating_mul(mass)
+}
+
+/// Fee at a fractional rate, rounded up to whole sompi.
+pub fn fee_at_rate(fee_rate: f64, mass: u64) -> u64 {
+    (fee_rate * mass as f64).ceil() as u64
+}
+
+/// Fee of a 1-in 1-out self-payment at `fee_rate`. Storage mass is negligible for
+/// this shape, so only compute mass is charged.
+pub fn required_fee_base(fee_rate: u64) -> u64 {
+    required_fee(fee_rate, compute_mass(1, 1, 0))
+}
+
+/// Mass of a split transaction spending `input_amount` into `num_outputs` outputs of
+/// `amount_per_output` plus change.
+pub fn splitting_mass(input_amount: u64, amount_per_output: u64, num_outputs: usize) -> u64 {
+    let mut outputs = vec![amount_per_output; num_outputs];
+    let change = input_amount.saturating_sub(amount_per_output * num_outputs as u64);
+    if change > 0 {
+        outputs.push(change);
+    }
+    estimated_mass(&[input_amount], &outputs, 0)
+}
+
+/// Fee of a split transaction at `fee_rate`, the configured or estimated rate the
+/// spam pays.
+pub fn required_fee_splitting(fee_rate: u64, input_amount: u64, amount_per_output: u64, num_outputs: usize) -> u64 {
+    required_fee(fee_rate, splitting_mass(input_amount, amount_per_output, num_outputs))
+}
+
+pub fn format_kas(sompi: u64) -> String {
+    format!("{:.8} KAS", sompi as f64 / SOMPI_PER_KASPA as f64)
+}
+
+// ----------------------- building -----------------------
+/// Builds, signs and finalizes a transaction spending `utxos` into `outputs`.
+pub fn build_signed_tx(keypair: Keypair, utxos: &[Utxo], outputs: Vec<TransactionOutput>, payload: Vec<u8>) -> Transaction {
+    let inputs = utxos.iter().map(|(outpoint, _)| TransactionInput::new(*outpoint, vec![], 0, 1)).collect();
+    let unsigned_tx = Transaction::new_non_finalized(TX_VERSION, inputs, outputs, 0, SUBNETWORK_ID_NATIVE, 0, payload);
+    let entries = utxos.iter().map(|(_, entry)| entry.clone()).collect();
+    let mut tx = sign(MutableTransaction::with_entries(unsigned_tx, entries), keypair).tx;
+    tx.finalize();
+    tx
+}
@@ -0,0 +1,56 @@
+use std::{
+    error::Error,
+    fs::File,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::Serialize;
+
+/// One spam transaction and the shape and parameters sampled for it.
+#[derive(Debug, Serialize)]
+pub struct TxRecord {
+    pub txid: String,
+    /// Unix time of the submission, in milliseconds
+    pub submitted_at_ms: u128,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub fee_rate: f64,
+    pub fee: u64,
+    pub mass: u64,
+    pub payload_bytes: usize,
+    /// Unconfirmed ancestors when submitted (`CHAIN_DEPTH`)
+    pub chain_depth: u32,
+    /// "accepted" or the node's rejection message
+    pub result: String,
+}
+
+/// CSV log of every submitted transaction (`TX_LOG_FILE`), so sampled fee rates and
+/// payload sizes can be correlated with inclusion latency afterwards.
+pub struct TxLog {
+    writer: Mutex<csv::Writer<File>>,
+}
+
+impl TxLog {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        let writer = csv::Writer::from_path(path).map_err(|e| format!("Cannot create {}: {}", path, e))?;
+        Ok(TxLog { writer: Mutex::new(writer) })
+    }
+
+    pub fn record(&self, record: &TxRecord) {
+        if let Err(e) = self.writer.lock().unwrap().serialize(record) {
+            warn!("Failed to write transaction log: {}", e);
+        }
+    }
+
+    pub fn flush(&self) {
+        if let Err(e) = self.writer.lock().unwrap().flush() {
+            warn!("Failed to flush transaction log: {}", e);
+        }
+    }
+}
+
+pub fn unix_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis()).unwrap_or_default()
+}
@@ -0,0 +1,847 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use kaspa_consensus_core::{
+    tx::{ScriptPublicKey, TransactionId, TransactionOutpoint, UtxoEntry},
+    Hash,
+};
+use serde::{Deserialize, Serialize};
+
+pub type Utxo = (TransactionOutpoint, UtxoEntry);
+
+/// Most confirmation lag samples kept until they are taken.
+const MAX_LAG_SAMPLES: usize = 100_000;
+/// How long a chained spend of an output the node has not reported yet stays reserved.
+/// Parent and child are often accepted in the same block, so the output never shows up.
+const CHAINED_RESERVATION_TTL: Duration = Duration::from_secs(60);
+
+// ----------------------- maturity -----------------------
+/// Confirmations (in DAA score) an output needs before we spend it, from
+/// `COINBASE_MATURITY` and `NON_COINBASE_CONFIRMATIONS`.
+#[derive(Debug, Clone, Copy)]
+pub struct Maturity {
+    pub coinbase: u64,
+    pub non_coinbase: u64,
+}
+
+pub fn is_utxo_spendable(entry: &UtxoEntry, virtual_daa_score: u64, maturity: Maturity) -> bool {
+    let needed_confs = if entry.is_coinbase { maturity.coinbase } else { maturity.non_coinbase };
+    entry.block_daa_score + needed_confs < virtual_daa_score
+}
+
+// ----------------------- pool -----------------------
+/// Counts reported by `UtxoPool::stats`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolStats {
+    pub available: usize,
+    pub available_amount: u64,
+    pub immature: usize,
+    pub reserved: usize,
+    pub pending_change: usize,
+}
+
+/// Totals over several pools, e.g. one per wallet.
+impl std::iter::Sum for PoolStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(PoolStats::default(), |total, stats| PoolStats {
+            available: total.available + stats.available,
+            available_amount: total.available_amount + stats.available_amount,
+            immature: total.immature + stats.immature,
+            reserved: total.reserved + stats.reserved,
+            pending_change: total.pending_change + stats.pending_change,
+        })
+    }
+}
+
+/// How far our view of the UTXO set was from the node's when `update_from_node`
+/// replaced it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Drift {
+    /// Outputs we held that the node no longer reports
+    pub missing: usize,
+    /// Outputs the node reports that we did not hold
+    pub unexpected: usize,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.missing == 0 && self.unexpected == 0
+    }
+}
+
+impl std::iter::Sum for Drift {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Drift::default(), |total, drift| Drift {
+            missing: total.missing + drift.missing,
+            unexpected: total.unexpected + drift.unexpected,
+        })
+    }
+}
+
+struct Reservation {
+    entry: UtxoEntry,
+    since: Instant,
+    /// The submitted transaction spending the output
+    spent_by: Option<TransactionId>,
+    /// Chain depth of a transaction spending the output, if it was reserved from
+    /// pending change
+    chain_depth: Option<u32>,
+    /// Whether the node has reported the output since it was reserved
+    seen: bool,
+    /// Spent by a mempool transaction that is not ours, see `UtxoPool::quarantine`
+    quarantined: bool,
+}
+
+/// An output of one of our in-flight transactions.
+struct PendingOutput {
+    entry: UtxoEntry,
+    since: Instant,
+    /// Chain depth of a transaction spending it: the creating transaction and its
+    /// unconfirmed ancestors
+    chain_depth: u32,
+}
+
+#[derive(Default)]
+struct PoolState {
+    virtual_daa_score: u64,
+    /// Outputs the node reports for our address, mature or not
+    confirmed: HashMap<TransactionOutpoint, UtxoEntry>,
+    /// Outputs handed out to a transaction being built or in flight
+    reserved: HashMap<TransactionOutpoint, Reservation>,
+    /// Outputs of our in-flight transactions the node does not report as spendable yet
+    pending_change: HashMap<TransactionOutpoint, PendingOutput>,
+    /// Mature, unreserved confirmed outputs in the order they are handed out
+    available: VecDeque<TransactionOutpoint>,
+    /// Time from submission until a transaction's first output became spendable
+    confirmation_lags: Vec<Duration>,
+    /// Requests for the node's UTXO set in flight, see `UtxoPool::begin_snapshot`
+    snapshots: usize,
+    /// Outputs notifications added (`Some`) or removed (`None`) while a snapshot was
+    /// in flight, which the snapshot may predate
+    changed_during_snapshot: HashMap<TransactionOutpoint, Option<UtxoEntry>>,
+}
+
+impl PoolState {
+    fn is_available(&self, outpoint: &TransactionOutpoint, maturity: Maturity) -> bool {
+        !self.reserved.contains_key(outpoint)
+            && self
+                .confirmed
+                .get(outpoint)
+                .is_some_and(|entry| is_utxo_spendable(entry, self.virtual_daa_score, maturity))
+    }
+
+    fn rebuild_available(&mut self, maturity: Maturity) {
+        let mut available: Vec<(TransactionOutpoint, u64)> = self
+            .confirmed
+            .iter()
+            .filter(|(outpoint, entry)| {
+                !self.reserved.contains_key(outpoint) && is_utxo_spendable(entry, self.virtual_daa_score, maturity)
+            })
+            .map(|(outpoint, entry)| (*outpoint, entry.amount))
+            .collect();
+        available.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        self.available = available.into_iter().map(|(outpoint, _)| outpoint).collect();
+    }
+
+    /// Stops treating pending change the node reports as spendable as pending, and
+    /// samples the confirmation lag of first outputs.
+    fn settle_pending(&mut self, maturity: Maturity) {
+        let PoolState { confirmed, pending_change, virtual_daa_score, confirmation_lags, .. } = self;
+        pending_change.retain(|outpoint, PendingOutput { since, .. }| {
+            let spendable =
+                confirmed.get(outpoint).is_some_and(|entry| is_utxo_spendable(entry, *virtual_daa_score, maturity));
+            if spendable && outpoint.index == 0 && confirmation_lags.len() < MAX_LAG_SAMPLES {
+                confirmation_lags.push(since.elapsed());
+            }
+            !spendable
+        });
+    }
+
+    fn end_snapshot(&mut self) {
+        self.snapshots = self.snapshots.saturating_sub(1);
+        if self.snapshots == 0 {
+            self.changed_during_snapshot.clear();
+        }
+    }
+
+    fn take_available(&mut self, maturity: Maturity) -> Option<Utxo> {
+        while let Some(outpoint) = self.available.pop_front() {
+            if self.is_available(&outpoint, maturity) {
+                let entry = self.confirmed[&outpoint].clone();
+                let reservation = Reservation {
+                    entry: entry.clone(),
+                    since: Instant::now(),
+                    spent_by: None,
+                    chain_depth: None,
+                    seen: true,
+                    quarantined: false,
+                };
+                self.reserved.insert(outpoint, reservation);
+                return Some((outpoint, entry));
+            }
+        }
+        None
+    }
+}
+
+/// Tracks which of our UTXOs are spendable, reserved by an in-flight transaction,
+/// or waiting to appear as change. Every operation holds a single lock, so two
+/// callers can never reserve the same outpoint.
+pub struct UtxoPool {
+    state: Mutex<PoolState>,
+    maturity: Maturity,
+    max_pending_age: Duration,
+}
+
+impl UtxoPool {
+    pub fn new(maturity: Maturity, max_pending_age: Duration) -> Self {
+        UtxoPool { state: Mutex::new(PoolState::default()), maturity, max_pending_age }
+    }
+
+    /// Marks the start of a request for the node's UTXO set. Its result goes to
+    /// `update_from_node`, or the request is ended with `cancel_snapshot` if it fails.
+    /// Notifications applied in between take precedence over the snapshot, which may
+    /// have been taken before them.
+    pub fn begin_snapshot(&self) {
+        self.state.lock().unwrap().snapshots += 1;
+    }
+
+    pub fn cancel_snapshot(&self) {
+        self.state.lock().unwrap().end_snapshot();
+    }
+
+    /// Replaces our view with the node's UTXO set, requested after `begin_snapshot`.
+    /// Reservations whose outpoint the node no longer reports have been spent and are
+    /// dropped, except chained spends of outputs the node has not reported yet;
+    /// pending change the node now reports as spendable is no longer pending. Returns
+    /// how far our view had drifted.
+    pub fn update_from_node(&self, utxos: Vec<Utxo>, virtual_daa_score: u64) -> Drift {
+        let mut state = self.state.lock().unwrap();
+        state.virtual_daa_score = state.virtual_daa_score.max(virtual_daa_score);
+        let mut utxos: HashMap<TransactionOutpoint, UtxoEntry> = utxos.into_iter().collect();
+        for (outpoint, change) in &state.changed_during_snapshot {
+            match change {
+                Some(entry) => utxos.insert(*outpoint, entry.clone()),
+                None => utxos.remove(outpoint),
+            };
+        }
+        state.end_snapshot();
+        let drift = Drift {
+            missing: state.confirmed.keys().filter(|outpoint| !utxos.contains_key(outpoint)).count(),
+            unexpected: utxos.keys().filter(|outpoint| !state.confirmed.contains_key(outpoint)).count(),
+        };
+        state.confirmed = utxos;
+        let PoolState { confirmed, reserved, .. } = &mut *state;
+        reserved.retain(|outpoint, reservation| {
+            if confirmed.contains_key(outpoint) {
+                reservation.seen = true;
+                return true;
+            }
+            !reservation.seen && reservation.since.elapsed() <= CHAINED_RESERVATION_TTL
+        });
+        state.settle_pending(self.maturity);
+        state.rebuild_available(self.maturity);
+        drift
+    }
+
+    /// Applies the outputs a UtxosChanged notification added to and removed from our
+    /// address. Reservations of removed outpoints have been spent and are dropped.
+    pub fn apply_changes(&self, added: Vec<Utxo>, removed: &[TransactionOutpoint]) {
+        let mut state = self.state.lock().unwrap();
+        let recording = state.snapshots > 0;
+        for outpoint in removed {
+            state.confirmed.remove(outpoint);
+            state.reserved.remove(outpoint);
+            if recording {
+                state.changed_during_snapshot.insert(*outpoint, None);
+            }
+        }
+        for (outpoint, entry) in added {
+            if let Some(reservation) = state.reserved.get_mut(&outpoint) {
+                reservation.seen = true;
+            }
+            if recording {
+                state.changed_during_snapshot.insert(outpoint, Some(entry.clone()));
+            }
+            state.confirmed.insert(outpoint, entry);
+        }
+        state.settle_pending(self.maturity);
+        state.rebuild_available(self.maturity);
+    }
+
+    /// Advances the DAA score used for maturity, e.g. from a VirtualDaaScoreChanged
+    /// notification, so outputs mature between refreshes.
+    pub fn set_virtual_daa_score(&self, virtual_daa_score: u64) {
+        let mut state = self.state.lock().unwrap();
+        if virtual_daa_score > state.virtual_daa_score {
+            state.virtual_daa_score = virtual_daa_score;
+            state.settle_pending(self.maturity);
+            state.rebuild_available(self.maturity);
+        }
+    }
+
+    /// Reserves exactly `num_inputs` available UTXOs, largest first, if together they
+    /// hold at least `min_total`.
+    pub fn reserve(&self, num_inputs: usize, min_total: u64) -> Option<Vec<Utxo>> {
+        let mut state = self.state.lock().unwrap();
+        let mut selected = Vec::with_capacity(num_inputs);
+        while selected.len() < num_inputs {
+            match state.take_available(self.maturity) {
+                Some(utxo) => selected.push(utxo),
+                None => break,
+            }
+        }
+        let total: u64 = selected.iter().map(|(_, entry)| entry.amount).sum();
+        if selected.len() == num_inputs && total >= min_total {
+            return Some(selected);
+        }
+        for (outpoint, _) in selected.iter().rev() {
+            state.reserved.remove(outpoint);
+            state.available.push_front(*outpoint);
+        }
+        None
+    }
+
+    /// Reserves exactly `num_inputs` outputs of our in-flight transactions, largest
+    /// first, if together they hold at least `min_total`, so a transaction can spend
+    /// them before they confirm. Only outputs that keep its chain depth, the number of
+    /// its unconfirmed ancestors, within `max_depth` qualify. Returns the inputs and
+    /// that chain depth.
+    pub fn reserve_chained(&self, num_inputs: usize, min_total: u64, max_depth: u32) -> Option<(Vec<Utxo>, u32)> {
+        let mut state = self.state.lock().unwrap();
+        let mut candidates: Vec<(TransactionOutpoint, u64)> = state
+            .pending_change
+            .iter()
+            .filter(|(outpoint, output)| output.chain_depth <= max_depth && !state.reserved.contains_key(outpoint))
+            .map(|(outpoint, output)| (*outpoint, output.entry.amount))
+            .collect();
+        if candidates.len() < num_inputs {
+            return None;
+        }
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        candidates.truncate(num_inputs);
+        if candidates.iter().map(|(_, amount)| amount).sum::<u64>() < min_total {
+            return None;
+        }
+        let now = Instant::now();
+        let mut selected = Vec::with_capacity(num_inputs);
+        let mut depth = 0;
+        for (outpoint, _) in candidates {
+            let output = state.pending_change.remove(&outpoint).unwrap();
+            depth = depth.max(output.chain_depth);
+            let seen = state.confirmed.contains_key(&outpoint);
+            let reservation = Reservation {
+                entry: output.entry.clone(),
+                since: now,
+                spent_by: None,
+                chain_depth: Some(output.chain_depth),
+                seen,
+                quarantined: false,
+            };
+            state.reserved.insert(outpoint, reservation);
+            selected.push((outpoint, output.entry));
+        }
+        Some((selected, depth))
+    }
+
+    /// Reserves up to `max_inputs` available UTXOs, largest first.
+    pub fn reserve_up_to(&self, max_inputs: usize) -> Vec<Utxo> {
+        let mut state = self.state.lock().unwrap();
+        let mut selected = Vec::new();
+        while selected.len() < max_inputs {
+            match state.take_available(self.maturity) {
+                Some(utxo) => selected.push(utxo),
+                None => break,
+            }
+        }
+        selected
+    }
+
+    /// Returns reserved outpoints to the pool, e.g. after the node rejected the
+    /// transaction spending them. Chained outputs go back to pending change and
+    /// quarantined ones stay reserved.
+    pub fn release(&self, outpoints: &[TransactionOutpoint]) {
+        let mut state = self.state.lock().unwrap();
+        for outpoint in outpoints {
+            if state.reserved.get(outpoint).is_some_and(|reservation| reservation.quarantined) {
+                continue;
+            }
+            let Some(reservation) = state.reserved.remove(outpoint) else { continue };
+            if state.is_available(outpoint, self.maturity) {
+                state.available.push_front(*outpoint);
+            } else if let Some(chain_depth) = reservation.chain_depth {
+                let output = PendingOutput { entry: reservation.entry, since: Instant::now(), chain_depth };
+                state.pending_change.insert(*outpoint, output);
+            }
+        }
+    }
+
+    /// Keeps reserved `outpoints` the node refused as already spent by a mempool
+    /// transaction, e.g. one we lost track of, out of the pool. The node keeps
+    /// reporting them until that transaction is accepted, so they stay reserved until
+    /// the node drops them.
+    pub fn quarantine(&self, outpoints: &[TransactionOutpoint]) {
+        let mut state = self.state.lock().unwrap();
+        for outpoint in outpoints {
+            if let Some(reservation) = state.reserved.get_mut(outpoint) {
+                reservation.quarantined = true;
+            }
+        }
+    }
+
+    /// Records that transaction `txid` spending `outpoints` was accepted by the node,
+    /// along with the outputs it creates for us.
+    pub fn mark_submitted(&self, txid: TransactionId, outpoints: &[TransactionOutpoint], change: Vec<Utxo>) {
+        self.mark_submitted_chained(txid, outpoints, change, 0);
+    }
+
+    /// Like `mark_submitted`, for a transaction with `chain_depth` unconfirmed
+    /// ancestors.
+    pub fn mark_submitted_chained(
+        &self,
+        txid: TransactionId,
+        outpoints: &[TransactionOutpoint],
+        change: Vec<Utxo>,
+        chain_depth: u32,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        for outpoint in outpoints {
+            if let Some(reservation) = state.reserved.get_mut(outpoint) {
+                reservation.spent_by = Some(txid);
+            }
+        }
+        let now = Instant::now();
+        for (outpoint, entry) in change {
+            state.pending_change.insert(outpoint, PendingOutput { entry, since: now, chain_depth: chain_depth + 1 });
+        }
+    }
+
+    /// Whether `outpoint` is an output of one of our in-flight transactions that is
+    /// not spendable yet.
+    pub fn is_pending(&self, outpoint: &TransactionOutpoint) -> bool {
+        self.state.lock().unwrap().pending_change.contains_key(outpoint)
+    }
+
+    /// Releases reservations never submitted nor quarantined and forgets pending
+    /// change older than `MAX_PENDING_AGE_SECS`. Returns how many entries were pruned. Submitted
+    /// reservations stay, as their transaction may still be in the mempool; see
+    /// `stale_submissions`.
+    pub fn prune_stale(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let max_age = self.max_pending_age;
+        let stale: Vec<TransactionOutpoint> = state
+            .reserved
+            .iter()
+            .filter(|(_, reservation)| {
+                reservation.spent_by.is_none() && !reservation.quarantined && reservation.since.elapsed() > max_age
+            })
+            .map(|(outpoint, _)| *outpoint)
+            .collect();
+        for outpoint in &stale {
+            state.reserved.remove(outpoint);
+            if state.is_available(outpoint, self.maturity) {
+                state.available.push_back(*outpoint);
+            }
+        }
+        let pending_before = state.pending_change.len();
+        state.pending_change.retain(|_, output| output.since.elapsed() <= max_age);
+        stale.len() + pending_before - state.pending_change.len()
+    }
+
+    /// Transactions submitted more than `MAX_PENDING_AGE_SECS` ago whose inputs the
+    /// node still reports unspent. Their inputs stay quarantined until the caller
+    /// finds a transaction gone from the mempool and calls `release_abandoned`.
+    pub fn stale_submissions(&self) -> Vec<TransactionId> {
+        let state = self.state.lock().unwrap();
+        let mut txids: Vec<TransactionId> = state
+            .reserved
+            .iter()
+            .filter(|(outpoint, reservation)| {
+                reservation.since.elapsed() > self.max_pending_age && state.confirmed.contains_key(outpoint)
+            })
+            .filter_map(|(_, reservation)| reservation.spent_by)
+            .collect();
+        txids.sort_unstable();
+        txids.dedup();
+        txids
+    }
+
+    /// Returns the inputs of `txid`, which the node no longer holds in its mempool,
+    /// to the pool. Returns how many were released.
+    pub fn release_abandoned(&self, txid: &TransactionId) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let abandoned: Vec<TransactionOutpoint> = state
+            .reserved
+            .iter()
+            .filter(|(outpoint, reservation)| {
+                reservation.spent_by.as_ref() == Some(txid) && state.confirmed.contains_key(outpoint)
+            })
+            .map(|(outpoint, _)| *outpoint)
+            .collect();
+        for outpoint in &abandoned {
+            state.reserved.remove(outpoint);
+            if state.is_available(outpoint, self.maturity) {
+                state.available.push_back(*outpoint);
+            }
+        }
+        abandoned.len()
+    }
+
+    /// Takes the confirmation lags recorded since the last call: how long our
+    /// transactions took from submission until the node reported them spendable.
+    pub fn take_confirmation_lags(&self) -> Vec<Duration> {
+        std::mem::take(&mut self.state.lock().unwrap().confirmation_lags)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let state = self.state.lock().unwrap();
+        let mut stats = PoolStats {
+            reserved: state.reserved.len(),
+            pending_change: state.pending_change.len(),
+            ..Default::default()
+        };
+        for (outpoint, entry) in &state.confirmed {
+            if state.reserved.contains_key(outpoint) {
+                continue;
+            }
+            if is_utxo_spendable(entry, state.virtual_daa_score, self.maturity) {
+                stats.available += 1;
+                stats.available_amount += entry.amount;
+            } else {
+                stats.immature += 1;
+            }
+        }
+        stats
+    }
+}
+
+// ----------------------- snapshots -----------------------
+#[derive(Serialize, Deserialize, Debug)]
+struct SnapshotRecord {
+    transaction_id: String,
+    index: u32,
+    amount: u64,
+    block_daa_score: u64,
+    is_coinbase: bool,
+    /// Virtual DAA score when the snapshot was taken; missing in older snapshots
+    #[serde(default)]
+    virtual_daa_score: u64,
+}
+
+/// Writes UTXOs to a CSV snapshot that can later be fed to `--dry-run --utxo-snapshot`,
+/// with the virtual DAA score their maturity is judged against.
+pub fn save_snapshot(utxos: &[Utxo], virtual_daa_score: u64, filename: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_path(filename)?;
+    for (outpoint, entry) in utxos {
+        wtr.serialize(SnapshotRecord {
+            transaction_id: outpoint.transaction_id.to_string(),
+            index: outpoint.index,
+            amount: entry.amount,
+            block_daa_score: entry.block_daa_score,
+            is_coinbase: entry.is_coinbase,
+            virtual_daa_score,
+        })?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Loads a CSV snapshot written by `save_snapshot`. Script public keys are not
+/// stored, so the entries are only suitable for planning, not for signing. Returns
+/// the virtual DAA score of the snapshot, if it has one.
+pub fn load_snapshot(filename: &str) -> Result<(Vec<Utxo>, Option<u64>), Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(filename)?;
+    let mut utxos = Vec::new();
+    let mut virtual_daa_score = 0;
+    for record in rdr.deserialize() {
+        let record: SnapshotRecord = record?;
+        virtual_daa_score = virtual_daa_score.max(record.virtual_daa_score);
+        let transaction_id = record
+            .transaction_id
+            .parse::<Hash>()
+            .map_err(|e| format!("Invalid transaction id {} in {}: {}", record.transaction_id, filename, e))?;
+        utxos.push((
+            TransactionOutpoint::new(transaction_id, record.index),
+            UtxoEntry::new(record.amount, ScriptPublicKey::from_vec(0, vec![]), record.block_daa_score, record.is_coinbase),
+        ));
+    }
+    Ok((utxos, (virtual_daa_score > 0).then_some(virtual_daa_score)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use rand::Rng;
+
+    use super::*;
+
+    const MATURITY: Maturity = Maturity { coinbase: 100, non_coinbase: 10 };
+    const VIRTUAL_DAA_SCORE: u64 = 1_000;
+    const FUND_UTXOS: u64 = 200;
+    const ROUNDS: usize = 2_000;
+
+    fn outpoint(id: u64, index: u32) -> TransactionOutpoint {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&id.to_le_bytes());
+        TransactionOutpoint::new(Hash::from_bytes(bytes), index)
+    }
+
+    fn entry(amount: u64) -> UtxoEntry {
+        UtxoEntry::new(amount, ScriptPublicKey::from_vec(0, vec![]), 0, false)
+    }
+
+    /// A node holding a UTXO set and a mempool of transactions spending from it.
+    #[derive(Default)]
+    struct Node {
+        utxos: HashMap<TransactionOutpoint, UtxoEntry>,
+        mempool: VecDeque<(Vec<TransactionOutpoint>, Vec<Utxo>)>,
+        next_txid: u64,
+    }
+
+    impl Node {
+        fn funded() -> Self {
+            let utxos = (1..=FUND_UTXOS).map(|id| (outpoint(id, 0), entry(1_000))).collect();
+            Node { utxos, mempool: VecDeque::new(), next_txid: FUND_UTXOS + 1 }
+        }
+
+        fn snapshot(&self) -> Vec<Utxo> {
+            self.utxos.iter().map(|(outpoint, entry)| (*outpoint, entry.clone())).collect()
+        }
+
+        /// Mines the oldest mempool transaction, returning the outputs it added and removed.
+        fn mine(&mut self) -> Option<(Vec<Utxo>, Vec<TransactionOutpoint>)> {
+            let (inputs, outputs) = self.mempool.pop_front()?;
+            for input in &inputs {
+                assert!(self.utxos.remove(input).is_some(), "{} mined twice", input);
+            }
+            self.utxos.extend(outputs.iter().cloned());
+            Some((outputs, inputs))
+        }
+    }
+
+    /// What the workers share: the pool under test, the node behind it, the outpoints
+    /// currently handed out and those already spent by a submitted transaction.
+    struct Harness {
+        pool: UtxoPool,
+        node: Mutex<Node>,
+        held: Mutex<HashSet<TransactionOutpoint>>,
+        spent: Mutex<HashSet<TransactionOutpoint>>,
+    }
+
+    impl Harness {
+        fn new(max_pending_age: Duration) -> Arc<Self> {
+            let node = Node::funded();
+            let pool = UtxoPool::new(MATURITY, max_pending_age);
+            pool.update_from_node(node.snapshot(), VIRTUAL_DAA_SCORE);
+            Arc::new(Harness {
+                pool,
+                node: Mutex::new(node),
+                held: Mutex::new(HashSet::new()),
+                spent: Mutex::new(HashSet::new()),
+            })
+        }
+
+        /// Reserves a few outpoints, then either releases them or submits a
+        /// transaction spending them into one or two outputs.
+        fn spend(&self, rng: &mut impl Rng) {
+            let Some(inputs) = self.pool.reserve(rng.gen_range(1..=2), 0) else { return };
+            let outpoints: Vec<TransactionOutpoint> = inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+            {
+                let mut held = self.held.lock().unwrap();
+                let spent = self.spent.lock().unwrap();
+                for outpoint in &outpoints {
+                    assert!(!spent.contains(outpoint), "{} handed out after it was spent", outpoint);
+                    assert!(held.insert(*outpoint), "{} handed out twice", outpoint);
+                }
+            }
+            if rng.gen_bool(0.3) {
+                self.held.lock().unwrap().retain(|outpoint| !outpoints.contains(outpoint));
+                self.pool.release(&outpoints);
+                return;
+            }
+            let total: u64 = inputs.iter().map(|(_, entry)| entry.amount).sum();
+            let mut node = self.node.lock().unwrap();
+            let id = node.next_txid;
+            node.next_txid += 1;
+            let txid = outpoint(id, 0).transaction_id;
+            let outputs: Vec<Utxo> = match rng.gen_bool(0.5) {
+                true => vec![(outpoint(id, 0), entry(total))],
+                false => vec![(outpoint(id, 0), entry(total / 2)), (outpoint(id, 1), entry(total - total / 2))],
+            };
+            node.mempool.push_back((outpoints.clone(), outputs.clone()));
+            self.pool.mark_submitted(txid, &outpoints, outputs);
+            drop(node);
+            self.spent.lock().unwrap().extend(outpoints.iter().copied());
+            self.held.lock().unwrap().retain(|outpoint| !outpoints.contains(outpoint));
+        }
+
+        /// Mines a transaction and applies it as a UtxosChanged notification, or
+        /// replaces the pool with a snapshot of the node.
+        fn sync(&self, rng: &mut impl Rng) {
+            let mut node = self.node.lock().unwrap();
+            if rng.gen_bool(0.8) {
+                if let Some((added, removed)) = node.mine() {
+                    self.pool.apply_changes(added, &removed);
+                }
+            } else {
+                let drift = self.pool.update_from_node(node.snapshot(), VIRTUAL_DAA_SCORE);
+                assert!(drift.is_empty(), "notifications left the pool off by {:?}", drift);
+            }
+        }
+
+        /// Asserts every output the node reports is available or reserved, never both,
+        /// and that pending change is exactly the outputs of the node's mempool.
+        fn check_accounting(&self) {
+            let node = self.node.lock().unwrap();
+            let state = self.pool.state.lock().unwrap();
+            let confirmed: HashSet<TransactionOutpoint> = state.confirmed.keys().copied().collect();
+            assert_eq!(confirmed, node.utxos.keys().copied().collect::<HashSet<_>>());
+            let available: HashSet<TransactionOutpoint> =
+                state.available.iter().filter(|outpoint| state.is_available(outpoint, MATURITY)).copied().collect();
+            for outpoint in &confirmed {
+                assert!(
+                    available.contains(outpoint) != state.reserved.contains_key(outpoint),
+                    "{} must be either available or reserved",
+                    outpoint
+                );
+            }
+            assert!(state.reserved.keys().all(|outpoint| confirmed.contains(outpoint)));
+            let in_flight: HashSet<TransactionOutpoint> =
+                node.mempool.iter().flat_map(|(_, outputs)| outputs.iter().map(|(outpoint, _)| *outpoint)).collect();
+            assert_eq!(state.pending_change.keys().copied().collect::<HashSet<_>>(), in_flight);
+        }
+
+        fn settle(&self) {
+            let mut node = self.node.lock().unwrap();
+            while let Some((added, removed)) = node.mine() {
+                self.pool.apply_changes(added, &removed);
+            }
+            let drift = self.pool.update_from_node(node.snapshot(), VIRTUAL_DAA_SCORE);
+            assert!(drift.is_empty());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_use_never_hands_out_an_outpoint_twice() {
+        let harness = Harness::new(Duration::from_secs(60));
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let harness = harness.clone();
+                tokio::spawn(async move {
+                    for _ in 0..ROUNDS {
+                        harness.spend(&mut rand::thread_rng());
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let harness = harness.clone();
+                thread::spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..ROUNDS {
+                        harness.spend(&mut rng);
+                        harness.sync(&mut rng);
+                        harness.pool.prune_stale();
+                        harness.check_accounting();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        harness.settle();
+        harness.check_accounting();
+        assert!(harness.held.lock().unwrap().is_empty());
+        let stats = harness.pool.stats();
+        assert_eq!(stats.reserved, 0);
+        assert_eq!(stats.pending_change, 0);
+        assert_eq!(stats.available_amount, FUND_UTXOS * 1_000);
+    }
+
+    #[test]
+    fn snapshot_taken_before_a_notification_does_not_revive_spent_outpoints() {
+        let harness = Harness::new(Duration::from_secs(60));
+        let mut rng = rand::thread_rng();
+        while harness.node.lock().unwrap().mempool.is_empty() {
+            harness.spend(&mut rng);
+        }
+        harness.pool.begin_snapshot();
+        let stale = harness.node.lock().unwrap().snapshot();
+        let (added, removed) = harness.node.lock().unwrap().mine().unwrap();
+        harness.pool.apply_changes(added, &removed);
+
+        harness.pool.update_from_node(stale, VIRTUAL_DAA_SCORE);
+        harness.check_accounting();
+        let handed_out = harness.pool.reserve_up_to(FUND_UTXOS as usize * 2);
+        assert!(handed_out.iter().all(|(outpoint, _)| !removed.contains(outpoint)));
+    }
+
+    #[test]
+    fn quarantine_keeps_outpoints_out_until_the_node_drops_them() {
+        let harness = Harness::new(Duration::ZERO);
+        let inputs: Vec<TransactionOutpoint> =
+            harness.pool.reserve(2, 0).unwrap().into_iter().map(|(outpoint, _)| outpoint).collect();
+        let (conflicting, other) = (inputs[0], inputs[1]);
+        harness.pool.quarantine(&[conflicting]);
+        harness.pool.release(&inputs);
+        thread::sleep(Duration::from_millis(1));
+        assert_eq!(harness.pool.prune_stale(), 0);
+
+        // The node still reports the outpoint while a transaction we do not know spends it
+        harness.pool.update_from_node(harness.node.lock().unwrap().snapshot(), VIRTUAL_DAA_SCORE);
+        let handed_out: Vec<TransactionOutpoint> =
+            harness.pool.reserve_up_to(FUND_UTXOS as usize).into_iter().map(|(outpoint, _)| outpoint).collect();
+        assert_eq!(handed_out.len(), FUND_UTXOS as usize - 1);
+        assert!(handed_out.contains(&other));
+        assert!(!handed_out.contains(&conflicting));
+        harness.pool.release(&handed_out);
+
+        let mut node = harness.node.lock().unwrap();
+        node.mempool.push_back((vec![conflicting], vec![]));
+        let (added, removed) = node.mine().unwrap();
+        drop(node);
+        harness.pool.apply_changes(added, &removed);
+        harness.check_accounting();
+        assert_eq!(harness.pool.stats().reserved, 0);
+        assert_eq!(harness.pool.stats().available, FUND_UTXOS as usize - 1);
+    }
+
+    #[test]
+    fn prune_keeps_submitted_reservations_until_abandoned() {
+        let harness = Harness::new(Duration::ZERO);
+        let unsubmitted = harness.pool.reserve(1, 0).unwrap()[0].0;
+        let submitted = harness.pool.reserve(1, 0).unwrap()[0].0;
+        let txid = outpoint(u64::MAX, 0).transaction_id;
+        harness.pool.mark_submitted(txid, &[submitted], vec![]);
+        thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(harness.pool.prune_stale(), 1);
+        let handed_out: Vec<TransactionOutpoint> =
+            harness.pool.reserve_up_to(FUND_UTXOS as usize).into_iter().map(|(outpoint, _)| outpoint).collect();
+        assert!(handed_out.contains(&unsubmitted));
+        assert!(!handed_out.contains(&submitted));
+        harness.pool.release(&handed_out);
+
+        assert_eq!(harness.pool.stale_submissions(), vec![txid]);
+        assert_eq!(harness.pool.release_abandoned(&txid), 1);
+        assert!(harness.pool.stale_submissions().is_empty());
+        assert_eq!(harness.pool.stats().available, FUND_UTXOS as usize);
+    }
+}
@@ -0,0 +1,45 @@
+use std::{error::Error, fs, sync::Arc};
+
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::ScriptPublicKey;
+use kaspa_txscript::pay_to_address_script;
+use secp256k1::Keypair;
+
+use crate::{config::Network, keys, utxo::UtxoPool};
+
+/// A key we spend from, with its own UTXO pool.
+#[derive(Clone)]
+pub struct Wallet {
+    pub keypair: Keypair,
+    pub address: Address,
+    pub script_public_key: ScriptPublicKey,
+    pub pool: Arc<UtxoPool>,
+}
+
+impl Wallet {
+    pub fn new(keypair: Keypair, network: Network, pool: Arc<UtxoPool>) -> Self {
+        let address = keys::address_for(&keypair, network);
+        Wallet { keypair, script_public_key: pay_to_address_script(&address), address, pool }
+    }
+}
+
+/// Reads the private keys of a wallet file written by `--gen-wallets`, in TXT or
+/// CSV format. Addresses are derived from the keys on the selected network; the
+/// address column of the file is not used.
+pub fn load_keys(path: &str) -> Result<Vec<Keypair>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read wallet file {}: {}", path, e))?;
+    let keys: Vec<&str> = if contents.trim_start().starts_with("ID,") {
+        contents.lines().skip(1).filter_map(|line| line.split(',').nth(1)).collect()
+    } else {
+        contents.lines().filter_map(|line| line.strip_prefix("Private Key (hex):")).collect()
+    };
+    if keys.is_empty() {
+        return Err(format!("No private keys in wallet file {}", path).into());
+    }
+    keys.into_iter()
+        .enumerate()
+        .map(|(index, key)| {
+            keys::keypair_from_hex(key).map_err(|e| format!("Wallet {} in {}: {}", index + 1, path, e).into())
+        })
+        .collect()
+}
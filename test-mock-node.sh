@@ -0,0 +1,198 @@
+#!/bin/bash
+
+# End-to-end tests against the mock node: every phase talks wRPC JSON to a
+# simulated node over a local socket, so no network or funded wallet is needed
+echo "🧪 Testing Against the Mock Node"
+echo "================================"
+echo ""
+
+export PRIVATE_KEY_HEX=$(printf '%064d' 1)
+export KASPA_NETWORK=testnet10
+export RUST_LOG=warn
+
+cargo build --quiet || exit 1
+
+expect() {
+    local description=$1 pattern=$2
+    if echo "$output" | grep -qE "$pattern"; then
+        echo "✅ $description"
+    else
+        echo "❌ $description: expected /$pattern/"
+        echo "$output" | tail -20
+        exit 1
+    fi
+}
+
+echo "1️⃣ Testing split and spam phases..."
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 2>&1)
+expect "Split phase created the missing UTXOs" "Splitting: 20 UTXOs missing"
+expect "Spam phase submitted transactions" "Submitted: +[1-9][0-9]*"
+expect "No transaction was rejected" "Failed: +0$"
+expect "Acceptance latency is measured" "Accepted: +[1-9][0-9]* \\(p50 [0-9.]+s, p90 [0-9.]+s, p99 [0-9.]+s, max [0-9.]+s\\)"
+expect "Every transaction was accepted" "Not accepted: +0$"
+
+echo "2️⃣ Testing rejection handling..."
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 --mock-reject-every 50 2>&1)
+expect "Rejected transactions are counted" "Failed: +[1-9][0-9]*"
+expect "Rejections are classified" "Rejections: +other [1-9][0-9]*"
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 --mock-reject-every 10 --mock-reject-kind orphan 2>&1)
+expect "Orphans are resubmitted" "Rejections: +orphan [1-9][0-9]*"
+expect "No orphan was lost" "Failed: +0$"
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 --mock-reject-every 10 --mock-reject-kind fee 2>&1)
+expect "Low fees are bumped and resubmitted" "Rejections: +fee too low [1-9][0-9]*"
+expect "No bumped transaction was lost" "Failed: +0$"
+
+echo "3️⃣ Testing transaction shapes..."
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    TX_SHAPES=2x2=1,1x3=1,4x1=1,max-mass=1 timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 2>&1)
+expect "Shape mix is applied" "Shapes: 2x2=1,1x3=1,4x1=1,mass:100000=1"
+expect "Shaped transactions are accepted" "Submitted: +[1-9][0-9]*"
+expect "No shaped transaction was rejected" "Failed: +0$"
+
+echo "4️⃣ Testing traffic profiles..."
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TPS_PROFILE=steps:5@2,20@3 SPAM_DURATION_SECONDS=5 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 2>&1)
+expect "Scheduler follows the profile" "Sending: steps 5@2s, 20@3s"
+expect "Profile run submitted transactions" "Submitted: +[1-9][0-9]*"
+
+echo "5️⃣ Testing the maximum TPS search..."
+output=$(TARGET_UTXO_COUNT=40 OUTPUTS_PER_TRANSACTION=10 timeout 180 cargo run --quiet -- --find-max-tps --with-mock-node \
+    --mock-latency-ms 200 --start-tps 5 --max-tps 20 --trial-secs 3 --cooldown-secs 1 2>&1)
+expect "Search ran its trials" "Trial 1: 5.0 TPS"
+expect "Search reports a maximum" "Maximum sustained TPS: [0-9.]+"
+
+echo "6️⃣ Testing scenario files..."
+scenario=$(mktemp --suffix=.toml)
+cat > "$scenario" <<'EOF'
+name = "mock"
+
+[[phase]]
+kind = "split"
+utxos = 20
+outputs_per_transaction = 10
+
+[[phase]]
+kind = "send"
+name = "warm-up"
+tps = 10
+duration_secs = 3
+
+[[phase]]
+kind = "send"
+name = "capped"
+tps = 20
+stop = { max_transactions = 15 }
+
+[[phase]]
+kind = "consolidate"
+EOF
+output=$(timeout 180 cargo run --quiet -- --scenario "$scenario" --with-mock-node --mock-latency-ms 200 2>&1)
+rm -f "$scenario"
+expect "Scenario lists its phases" "Phases: +split → warm-up → capped → consolidate"
+expect "Stop condition ends a phase" "capped +.*stopped: 15 transactions submitted"
+expect "Scenario ran to the end" "Scenario finished"
+
+echo "7️⃣ Testing multi-wallet fan-out..."
+wallet_dir=$(mktemp -d)
+cargo run --quiet -- --gen-wallets --count 4 --format csv --output "$wallet_dir/wallets" > /dev/null || exit 1
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 \
+    WALLETS_FILE="$wallet_dir/wallets.csv" timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 2>&1)
+rm -rf "$wallet_dir"
+expect "Wallet file is loaded" "Wallets: 4 from"
+expect "Master key funds every wallet" "Funding 4 wallets with 5 UTXOs each: 20 UTXOs missing"
+expect "Wallets submitted transactions" "Submitted: +[1-9][0-9]*"
+expect "No wallet transaction was rejected" "Failed: +0$"
+
+echo "8️⃣ Testing wallet funding, balances and sweeping..."
+wallet_dir=$(mktemp -d)
+cargo run --quiet -- --gen-wallets --count 5 --format csv --output "$wallet_dir/wallets" > /dev/null || exit 1
+port=$((20000 + RANDOM % 10000))
+./target/debug/kaspa-tx-generator --mock-node --mock-listen "127.0.0.1:$port" --mock-latency-ms 200 > /dev/null 2>&1 &
+wallets_pid=$!
+trap 'kill $wallets_pid 2>/dev/null' EXIT
+sleep 2
+output=$(KASPA_RPC_URL="ws://127.0.0.1:$port" WRPC_ENCODING=json timeout 120 cargo run --quiet -- \
+    --fund-wallets "$wallet_dir/wallets.csv" --amount 10 2>&1)
+expect "Payments are batched into one transaction" "1 transactions for 5 wallets"
+expect "Every wallet was funded" "Funded 5 of 5 wallets"
+master=$(echo "$output" | grep -oE "from kaspatest:[a-z0-9]+" | cut -d' ' -f2)
+output=$(cat "$wallet_dir/wallets-funding.csv" 2>/dev/null)
+expect "Report lists a txid per address" "^5,kaspatest:[a-z0-9]+,1000000000,[0-9a-f]{64},accepted$"
+output=$(KASPA_RPC_URL="ws://127.0.0.1:$port" WRPC_ENCODING=json timeout 60 cargo run --quiet -- \
+    --balances "$wallet_dir/wallets.csv" 2>&1)
+expect "Balances list every wallet" "Balances of 5 addresses"
+expect "Balances add up the payments" "Total: +50\.00000000 KAS in 5 UTXOs"
+output=$(KASPA_RPC_URL="ws://127.0.0.1:$port" WRPC_ENCODING=json timeout 60 cargo run --quiet -- \
+    --balances "$master" --json 2>/dev/null)
+expect "Balances print JSON" "\"utxos\": [1-9][0-9]*"
+expect "Balances report mempool outputs apart from immature ones" "\"pending_sompi\": 0"
+# Let the payments mature before sweeping them back
+sleep 2
+output=$(KASPA_RPC_URL="ws://127.0.0.1:$port" WRPC_ENCODING=json timeout 120 cargo run --quiet -- \
+    --sweep-wallets "$wallet_dir/wallets.csv" --to "$master" 2>&1)
+kill $wallets_pid 2>/dev/null
+rm -rf "$wallet_dir"
+expect "Every wallet was swept" "Wallets swept: 5 of 5"
+expect "Sweep reports the amount per wallet" "swept 9\.[0-9]+ KAS from 1 UTXOs in 1 transactions"
+
+echo "9️⃣ Testing consolidation..."
+output=$(timeout 120 cargo run --quiet -- --consolidate --with-mock-node --mock-fund-utxos 200 --mock-latency-ms 200 2>&1)
+expect "200 UTXOs merged in two rounds" "Round 2: 1 transactions"
+expect "Consolidation finished" "Transactions: +4"
+
+echo "🔟 Testing the standalone mock node..."
+port=$((20000 + RANDOM % 10000))
+./target/debug/kaspa-tx-generator --mock-node --mock-listen "127.0.0.1:$port" --mock-fund-utxos 5 > /dev/null 2>&1 &
+mock_pid=$!
+trap 'kill $mock_pid 2>/dev/null' EXIT
+sleep 2
+output=$(KASPA_RPC_URL="ws://127.0.0.1:$port" WRPC_ENCODING=json timeout 60 cargo run --quiet -- --dry-run 2>&1)
+expect "Dry run reads the mock UTXO set over wRPC JSON" "Spendable: +5 "
+
+echo "🔁 Testing replace-by-fee..."
+report=$(mktemp --suffix=.csv)
+output=$(TARGET_UTXO_COUNT=5 OUTPUTS_PER_TRANSACTION=5 timeout 120 cargo run --quiet -- --rbf --with-mock-node \
+    --mock-latency-ms 2000 --rbf-tests 3 --rbf-replacements 2 --rbf-delays-ms 200 --report "$report" 2>&1)
+expect "Every replacement is taken" "Replacements: 6 of 6 taken by the mempool"
+expect "The last version is accepted" "Latest version accepted: 3 of 3 tests"
+output=$(cat "$report")
+rm -f "$report"
+expect "Report marks the accepted version" "^3,2,[0-9a-f]{64},[0-9.]+,[0-9]+,[0-9]+,[0-9]+,replaced v1,true$"
+
+echo "⛓️ Testing chained spends..."
+output=$(TARGET_UTXO_COUNT=5 OUTPUTS_PER_TRANSACTION=5 TARGET_TPS=10 SPAM_DURATION_SECONDS=5 CHAIN_DEPTH=3 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 2000 2>&1)
+expect "In-flight outputs are spent" "Chain depth 3: [1-9][0-9]* accepted"
+expect "No chained transaction was rejected" "Failed: +0$"
+expect "Every chained transaction was accepted" "Not accepted: +0$"
+
+echo "🔋 Testing pool replenishment..."
+output=$(TARGET_UTXO_COUNT=5 OUTPUTS_PER_TRANSACTION=5 TARGET_TPS=20 SPAM_DURATION_SECONDS=8 REPLENISH_LOW_WATER=3 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 1000 2>&1)
+expect "Large outputs are split during the run" "Replenished: +[1-9][0-9]* UTXOs in [1-9][0-9]* splits"
+expect "No transaction was rejected while replenishing" "Failed: +0$"
+
+echo "📬 Testing UtxosChanged updates..."
+# With polling effectively off, only notifications can return spent outputs to the pool
+output=$(TARGET_UTXO_COUNT=5 OUTPUTS_PER_TRANSACTION=5 TARGET_TPS=5 SPAM_DURATION_SECONDS=8 UTXO_REFRESH_SECS=3600 \
+    UTXO_RECONCILE_SECS=3600 timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 2>&1)
+expect "Outputs are reused from notifications" "Submitted: +([6-9]|[1-9][0-9]+)$"
+expect "No transaction was rejected with event updates" "Failed: +0$"
+
+echo "🔌 Testing dropped connections..."
+# Every 10th submit reaches the mempool but its connection closes before the answer;
+# with two connections, the one carrying the notifications is dropped too
+output=$(TARGET_UTXO_COUNT=20 OUTPUTS_PER_TRANSACTION=10 TARGET_TPS=10 SPAM_DURATION_SECONDS=10 CLIENT_POOL_SIZE=2 \
+    timeout 120 cargo run --quiet -- --with-mock-node --mock-latency-ms 200 --mock-drop-every 10 2>&1)
+expect "Requests on a dropped connection are requeued" "dropped .*, requeueing request"
+expect "Requeued submits the node already holds count as submitted" "Rejections: +.*already in mempool [1-9][0-9]*"
+expect "Notifications are resubscribed after a drop" "Node notifications (lost with the connection|ended), resubscribing"
+expect "No requeued transaction was lost" "Failed: +0$"
+expect "Requeued transactions were accepted" "Not accepted: +0$"
+
+echo ""
+echo "🎉 All mock node tests passed!"
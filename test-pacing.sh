@@ -0,0 +1,39 @@
+#!/bin/bash
+
+# Pacing test: the dry run simulates 60s of the send loop on a virtual clock,
+# so the released transaction count is deterministic
+echo "🧪 Testing TPS Pacing"
+echo "====================="
+echo ""
+
+snapshot=$(mktemp --suffix=.csv)
+trap 'rm -f "$snapshot"' EXIT
+echo "transaction_id,index,amount,block_daa_score,is_coinbase" > "$snapshot"
+echo "$(printf '%064d' 1),0,100000000000,1,false" >> "$snapshot"
+
+check_pacing() {
+    local description=$1 expected=$2
+    shift 2
+    output=$(env "$@" cargo run -- --dry-run --utxo-snapshot "$snapshot" 2>/dev/null)
+    if echo "$output" | grep -q "Simulated pacing: *$expected tx in 60s"; then
+        echo "✅ $description: $expected tx"
+    else
+        echo "❌ $description: expected $expected tx"
+        echo "$output" | grep "Simulated pacing"
+        exit 1
+    fi
+}
+
+echo "1️⃣ Testing integer TPS..."
+check_pacing "50 TPS, 10ms ticks" 3000 TARGET_TPS=50 MILLIS_PER_TICK=10
+
+echo "2️⃣ Testing fractional quota per tick..."
+check_pacing "37 TPS, 10ms ticks" 2220 TARGET_TPS=37 MILLIS_PER_TICK=10
+check_pacing "7 TPS, 333ms ticks" 420 TARGET_TPS=7 MILLIS_PER_TICK=333
+
+echo "3️⃣ Testing safety cap..."
+check_pacing "500 TPS capped (UNLEASHED=false)" 6000 TARGET_TPS=500 UNLEASHED=false MILLIS_PER_TICK=10
+check_pacing "500 TPS unleashed" 30000 TARGET_TPS=500 UNLEASHED=true MILLIS_PER_TICK=10
+
+echo ""
+echo "🎉 All pacing tests passed!"